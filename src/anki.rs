@@ -0,0 +1,501 @@
+//! # `anki`
+//!
+//! Anki decks (`.apkg`) with one note per highlight. An `.apkg` is a zip holding a SQLite
+//! collection in the legacy schema that every Anki version can import. Note GUIDs are the highlight
+//! IDs, so importing a re-export updates the existing notes instead of adding duplicates.
+//! Highlights of another book that share an ID get the book's ID appended to their GUID.
+
+use std::{
+    collections::HashSet,
+    error::Error,
+    io::{Cursor, Write},
+};
+
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+use rusqlite::{params, Connection, DatabaseName};
+use serde_json::{json, Value};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use crate::{
+    highlight_id::book_id,
+    model::{BooxFile, Highlight, Section},
+    text::{escape_html, extract_tags},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Parent deck of everything exported
+const ROOT_DECK: &str = "Highlights";
+
+const BASIC_MODEL_ID: i64 = 1_680_000_000_001;
+const CLOZE_MODEL_ID: i64 = 1_680_000_000_002;
+const FIELDS: [&str; 5] = ["Highlight", "Book", "Chapter", "Page", "Note"];
+
+const BACK: &str = "<hr id=answer>\n\
+    <div class=source>{{Book}}{{#Chapter}} · {{Chapter}}{{/Chapter}} · p. {{Page}}</div>\n\
+    {{#Note}}<div class=note>{{Note}}</div>{{/Note}}";
+const CSS: &str = ".card { font-family: Georgia, serif; font-size: 20px; text-align: left; }\n\
+    .source { color: #656d76; font-size: 16px; }\n\
+    .note { margin-top: 1em; padding-left: 0.5em; border-left: 3px solid #4a83c4; }\n\
+    .cloze { font-weight: bold; color: #4a83c4; }";
+
+const SCHEMA: &str = "
+CREATE TABLE col (
+    id integer primary key, crt integer not null, mod integer not null, scm integer not null,
+    ver integer not null, dty integer not null, usn integer not null, ls integer not null,
+    conf text not null, models text not null, decks text not null, dconf text not null,
+    tags text not null
+);
+CREATE TABLE notes (
+    id integer primary key, guid text not null, mid integer not null, mod integer not null,
+    usn integer not null, tags text not null, flds text not null, sfld integer not null,
+    csum integer not null, flags integer not null, data text not null
+);
+CREATE TABLE cards (
+    id integer primary key, nid integer not null, did integer not null, ord integer not null,
+    mod integer not null, usn integer not null, type integer not null, queue integer not null,
+    due integer not null, ivl integer not null, factor integer not null, reps integer not null,
+    lapses integer not null, left integer not null, odue integer not null,
+    odid integer not null, flags integer not null, data text not null
+);
+CREATE TABLE revlog (
+    id integer primary key, cid integer not null, usn integer not null, ease integer not null,
+    ivl integer not null, lastIvl integer not null, factor integer not null,
+    time integer not null, type integer not null
+);
+CREATE TABLE graves (usn integer not null, oid integer not null, type integer not null);
+CREATE INDEX ix_notes_usn on notes (usn);
+CREATE INDEX ix_cards_usn on cards (usn);
+CREATE INDEX ix_revlog_usn on revlog (usn);
+CREATE INDEX ix_cards_nid on cards (nid);
+CREATE INDEX ix_cards_sched on cards (did, queue, due);
+CREATE INDEX ix_revlog_cid on revlog (cid);
+CREATE INDEX ix_notes_csum on notes (csum);
+";
+
+/// What to hide on the front of the cards
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cloze {
+    /// Every occurrence of a phrase
+    Phrase(String),
+    /// The text of the note, for notes that repeat the words to learn
+    Note,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DeckLayout {
+    /// A subdeck per book
+    #[default]
+    Book,
+    /// A single deck for all books
+    Library,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AnkiOptions {
+    pub cloze: Option<Cloze>,
+    pub decks: DeckLayout,
+    /// When the package is written, the modification time of the notes of books whose
+    /// export time isn't known
+    pub written_at: NaiveDateTime,
+}
+
+/// Packages the highlights of `books` as an `.apkg`.
+/// Highlights without a match for the cloze become plain question and answer notes, and a book
+/// passed in more than once only gets the notes of its first copy.
+pub fn to_apkg(books: &[BooxFile], options: &AnkiOptions) -> Result<Vec<u8>> {
+    let db = Connection::open_in_memory()?;
+    db.execute_batch(SCHEMA)?;
+
+    let mut decks = vec![];
+    let mut due = 0;
+    let mut guids = HashSet::new();
+    let mut seen = HashSet::new();
+    for boox in books {
+        let book_id = book_id(&boox.metadata);
+        let deck_name = match options.decks {
+            DeckLayout::Book => format!("{ROOT_DECK}::{}", boox.metadata.title.trim()),
+            DeckLayout::Library => ROOT_DECK.to_owned(),
+        };
+        let deck_id = derive_id(&deck_name);
+        if !decks.iter().any(|(id, _)| *id == deck_id) {
+            decks.push((deck_id, deck_name));
+        }
+
+        let mut chapter = "";
+        for section in &boox.sections {
+            let highlight = match section {
+                Section::Chapter(c) => {
+                    chapter = &c.title;
+                    continue;
+                }
+                Section::HL(highlight) => highlight,
+            };
+            // The same book passed in twice
+            if !seen.insert((book_id.clone(), highlight.id.as_str())) {
+                continue;
+            }
+
+            let guid = if guids.insert(highlight.id.clone()) {
+                highlight.id.clone()
+            } else {
+                format!("{}-{book_id}", highlight.id)
+            };
+            due += 1;
+            let note = Note {
+                guid: &guid,
+                chapter,
+                highlight,
+            };
+            insert_note(&db, boox, &note, deck_id, due, options)?;
+        }
+    }
+
+    let modified = books
+        .iter()
+        .flat_map(|b| &b.sections)
+        .filter_map(|s| match s {
+            Section::HL(h) => Some(h.timestamp),
+            Section::Chapter(_) => None,
+        })
+        .max()
+        .map_or(0, |t| t.and_utc().timestamp());
+    let default_deck = decks.first().map_or(1, |(id, _)| *id);
+
+    db.execute(
+        "INSERT INTO col VALUES (1, ?1, ?2, ?2, 11, 0, 0, 0, ?3, ?4, ?5, ?6, '{}')",
+        params![
+            modified,
+            modified * 1000,
+            collection_config(default_deck).to_string(),
+            models(default_deck, modified).to_string(),
+            deck_table(&decks, modified).to_string(),
+            deck_options().to_string(),
+        ],
+    )?;
+
+    let collection = db.serialize(DatabaseName::Main)?;
+
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    zip.start_file("collection.anki2", SimpleFileOptions::default())?;
+    zip.write_all(&collection)?;
+    zip.start_file("media", SimpleFileOptions::default())?;
+    zip.write_all(b"{}")?;
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// A highlight with what it needs from around it to become a note
+struct Note<'a> {
+    guid: &'a str,
+    chapter: &'a str,
+    highlight: &'a Highlight,
+}
+
+fn insert_note(
+    db: &Connection,
+    boox: &BooxFile,
+    note: &Note,
+    deck_id: i64,
+    due: i64,
+    options: &AnkiOptions,
+) -> Result<()> {
+    let Note {
+        guid,
+        chapter,
+        highlight,
+    } = *note;
+    let phrase = match &options.cloze {
+        Some(Cloze::Phrase(phrase)) => Some(phrase.as_str()),
+        Some(Cloze::Note) => highlight.note.as_deref().map(str::trim),
+        None => None,
+    }
+    .filter(|p| !p.is_empty() && highlight.highlight.contains(p));
+
+    let (model_id, front) = match phrase {
+        Some(phrase) => (
+            CLOZE_MODEL_ID,
+            highlight
+                .highlight
+                .replace(phrase, &format!("{{{{c1::{phrase}}}}}")),
+        ),
+        None => (BASIC_MODEL_ID, highlight.highlight.clone()),
+    };
+
+    let fields = [
+        front.as_str(),
+        &boox.metadata.title,
+        chapter,
+        &highlight.page.to_string(),
+        highlight.note.as_deref().unwrap_or_default(),
+    ]
+    .map(|field| escape_html(field.trim()).replace('\n', "<br>"))
+    .join("\u{1f}");
+
+    let tags: String = extract_tags(highlight.note.iter().map(String::as_str))
+        .iter()
+        .map(|t| format!(" {t}"))
+        .chain([" highlight-extract ".to_owned()])
+        .collect();
+    // Like Anki, the checksum for finding duplicates is of the first field as it is, while
+    // the browser sorts by the text without cloze markup
+    let sort_field = highlight.highlight.trim();
+    let checksum = i64::from_str_radix(
+        &format!("{:x}", Sha1::digest(front.trim().as_bytes()))[..8],
+        16,
+    )?;
+    let note_id = derive_id(guid);
+    // Anki only updates notes that were modified after the copy in the collection, so an
+    // edited note in a newer export needs a newer time than the highlight's own
+    let modified = boox
+        .provenance
+        .as_ref()
+        .and_then(|p| p.exported_at)
+        .unwrap_or(options.written_at)
+        .and_utc()
+        .timestamp();
+
+    db.execute(
+        "INSERT INTO notes VALUES (?1, ?2, ?3, ?4, -1, ?5, ?6, ?7, ?8, 0, '')",
+        params![note_id, guid, model_id, modified, tags, fields, sort_field, checksum],
+    )?;
+    db.execute(
+        "INSERT INTO cards VALUES (?1, ?1, ?2, 0, ?3, -1, 0, 0, ?4, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+        params![note_id, deck_id, modified, due],
+    )?;
+
+    Ok(())
+}
+
+/// Anki wants positive 64 bit IDs; 52 bits of the hash keep them clear of the sign bit
+fn derive_id(key: &str) -> i64 {
+    let hash = format!("{:x}", Sha256::digest(key.as_bytes()));
+    i64::from_str_radix(&hash[..13], 16).expect("hex digits")
+}
+
+fn collection_config(deck_id: i64) -> Value {
+    json!({
+        "activeDecks": [deck_id],
+        "curDeck": deck_id,
+        "newSpread": 0,
+        "collapseTime": 1200,
+        "timeLim": 0,
+        "estTimes": true,
+        "dueCounts": true,
+        "curModel": BASIC_MODEL_ID.to_string(),
+        "nextPos": 1,
+        "sortType": "noteFld",
+        "sortBackwards": false,
+        "addToCur": true,
+    })
+}
+
+fn models(deck_id: i64, modified: i64) -> Value {
+    let fields: Vec<Value> = FIELDS
+        .iter()
+        .enumerate()
+        .map(|(ord, name)| {
+            json!({
+                "name": name, "ord": ord, "sticky": false, "rtl": false,
+                "font": "Arial", "size": 20, "media": [],
+            })
+        })
+        .collect();
+    let model = |id: i64, name: &str, kind: u8, front: &str, back: String| {
+        json!({
+            "id": id,
+            "name": name,
+            "type": kind,
+            "mod": modified,
+            "usn": -1,
+            "sortf": 0,
+            "did": deck_id,
+            "tmpls": [{
+                "name": "Card 1", "ord": 0, "qfmt": front, "afmt": back,
+                "did": null, "bqfmt": "", "bafmt": "",
+            }],
+            "flds": fields,
+            "css": CSS,
+            "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+            "latexPost": "\\end{document}",
+            "req": [[0, "any", [0]]],
+            "tags": [],
+            "vers": [],
+        })
+    };
+
+    json!({
+        BASIC_MODEL_ID.to_string(): model(
+            BASIC_MODEL_ID,
+            "highlight-extract Highlight",
+            0,
+            "{{Highlight}}",
+            format!("{{{{FrontSide}}}}\n{BACK}"),
+        ),
+        CLOZE_MODEL_ID.to_string(): model(
+            CLOZE_MODEL_ID,
+            "highlight-extract Cloze",
+            1,
+            "{{cloze:Highlight}}",
+            format!("{{{{cloze:Highlight}}}}\n{BACK}"),
+        ),
+    })
+}
+
+fn deck_table(decks: &[(i64, String)], modified: i64) -> Value {
+    let deck = |id: i64, name: &str| {
+        json!({
+            "id": id, "name": name, "desc": "", "mod": modified, "usn": -1,
+            "collapsed": false, "dyn": 0, "conf": 1, "extendNew": 10, "extendRev": 50,
+            "newToday": [0, 0], "revToday": [0, 0], "lrnToday": [0, 0], "timeToday": [0, 0],
+        })
+    };
+
+    let mut table = serde_json::Map::new();
+    table.insert("1".to_owned(), deck(1, "Default"));
+    for (id, name) in decks {
+        table.insert(id.to_string(), deck(*id, name));
+    }
+
+    Value::Object(table)
+}
+
+fn deck_options() -> Value {
+    json!({
+        "1": {
+            "id": 1, "name": "Default", "mod": 0, "usn": 0, "dyn": false,
+            "maxTaken": 60, "timer": 0, "autoplay": true, "replayq": true,
+            "new": {
+                "perDay": 20, "delays": [1, 10], "ints": [1, 4, 7], "initialFactor": 2500,
+                "separate": true, "order": 1, "bury": true,
+            },
+            "rev": {
+                "perDay": 100, "ease4": 1.3, "fuzz": 0.05, "minSpace": 1, "ivlFct": 1,
+                "maxIvl": 36500, "bury": true,
+            },
+            "lapse": {
+                "delays": [10], "mult": 0, "minInt": 1, "leechFails": 8, "leechAction": 0,
+            },
+        }
+    })
+}
+
+#[test]
+fn apkg_test() {
+    use std::io::Read;
+
+    use crate::parse_boox::parse_boox;
+
+    use crate::model::Provenance;
+
+    let (_, mut data) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    data.provenance = Some(Provenance::new(
+        Some(std::path::Path::new(
+            "Second-Brain-annotation-2023-05-01_10_00_00.txt",
+        )),
+        "",
+    ));
+    let (_, v1) = parse_boox(include_str!("../test/data/v1.txt")).unwrap();
+    let written_at = chrono::NaiveDate::from_ymd_opt(2024, 1, 1)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap();
+    let options = AnkiOptions {
+        cloze: Some(Cloze::Phrase("personal knowledge".to_owned())),
+        decks: DeckLayout::Book,
+        written_at,
+    };
+
+    let books = [data, v1];
+    let apkg = to_apkg(&books, &options).unwrap();
+    assert_eq!(apkg, to_apkg(&books, &options).unwrap());
+
+    let mut zip = zip::ZipArchive::new(Cursor::new(apkg)).unwrap();
+    let mut collection = vec![];
+    zip.by_name("collection.anki2")
+        .unwrap()
+        .read_to_end(&mut collection)
+        .unwrap();
+
+    let path = std::env::temp_dir().join(format!("apkg_test_{}.anki2", std::process::id()));
+    std::fs::write(&path, collection).unwrap();
+    let db = Connection::open(&path).unwrap();
+
+    let notes: Vec<(String, i64, String, i64, String)> = db
+        .prepare("SELECT guid, mid, flds, mod, sfld FROM notes ORDER BY id")
+        .unwrap()
+        .query_map([], |r| {
+            Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?))
+        })
+        .unwrap()
+        .map(|n| n.unwrap())
+        .collect();
+    let decks: i64 = db
+        .query_row("SELECT count(DISTINCT did) FROM cards", [], |r| r.get(0))
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(notes.len(), 8);
+    assert_eq!(decks, 2);
+
+    let pkm = notes.iter().find(|n| n.0 == "30aa3585d76d8ebd").unwrap();
+    assert_eq!(pkm.1, CLOZE_MODEL_ID);
+    assert_eq!(pkm.3, 1_682_935_200);
+    assert!(notes
+        .iter()
+        .filter(|n| n.2.contains("One Up on Wall Street"))
+        .all(|n| n.3 == written_at.and_utc().timestamp()));
+    assert_eq!(
+        pkm.2,
+        "PKM—or {{c1::personal knowledge}} management\u{1f}Building a Second Brain -- A Proven Method\u{1f}\u{1f}6\u{1f}"
+    );
+    assert_eq!(pkm.4, "PKM—or personal knowledge management");
+
+    let bookmark = notes.iter().find(|n| n.0 == "93d7c1a793812574").unwrap();
+    assert_eq!(bookmark.1, BASIC_MODEL_ID);
+    assert!(bookmark
+        .2
+        .ends_with("\u{1f}32\u{1f}There&#39;s too many to <br>actually read them all"));
+}
+
+#[test]
+fn shared_id_test() {
+    use std::io::Read;
+
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    let (_, mut edition) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    edition.metadata.title = "Building a Second Brain (2nd Edition)".to_owned();
+
+    let apkg = to_apkg(&[boox, edition], &AnkiOptions::default()).unwrap();
+    let mut zip = zip::ZipArchive::new(Cursor::new(apkg)).unwrap();
+    let mut collection = vec![];
+    zip.by_name("collection.anki2")
+        .unwrap()
+        .read_to_end(&mut collection)
+        .unwrap();
+
+    let path = std::env::temp_dir().join(format!("shared_id_test_{}.anki2", std::process::id()));
+    std::fs::write(&path, collection).unwrap();
+    let db = Connection::open(&path).unwrap();
+    let guids: Vec<String> = db
+        .prepare("SELECT guid FROM notes WHERE guid LIKE '30aa3585d76d8ebd%' ORDER BY guid")
+        .unwrap()
+        .query_map([], |r| r.get(0))
+        .unwrap()
+        .map(|g| g.unwrap())
+        .collect();
+    let cards: i64 = db
+        .query_row("SELECT count(*) FROM cards", [], |r| r.get(0))
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(guids.len(), 2);
+    assert_eq!(guids[0], "30aa3585d76d8ebd");
+    assert!(guids[1].starts_with("30aa3585d76d8ebd-"));
+    assert_eq!(cards, 8);
+}
@@ -0,0 +1,148 @@
+//! # `batch`
+//!
+//! Turns the input arguments into the files to convert, and names the file written for each book.
+//! Directories are searched recursively for exports, so the device's `note` or export folder can
+//! be passed as is, and patterns are expanded for shells that don't do it themselves.
+
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use walkdir::WalkDir;
+
+use crate::{model::BooxFile, slug::safe_path};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// The extension of the text exports written by the device
+const EXPORT_EXTENSION: &str = "txt";
+const GLOB_CHARS: &[char] = &['*', '?', '['];
+
+pub const DEFAULT_FILE_NAME: &str = "{author} - {title}.{ext}";
+const UNKNOWN_AUTHOR: &str = "Unknown";
+
+/// The files for one input argument: a file as it is, the exports below a directory in
+/// path order, or the files matching a glob pattern
+pub fn expand_input(input: &Path) -> Result<Vec<PathBuf>> {
+    if input.is_dir() {
+        let mut files = vec![];
+        for entry in WalkDir::new(input).sort_by_file_name() {
+            let entry = entry?;
+            let is_export = entry
+                .path()
+                .extension()
+                .is_some_and(|e| e == EXPORT_EXTENSION);
+            if entry.file_type().is_file() && is_export {
+                files.push(entry.into_path());
+            }
+        }
+        if files.is_empty() {
+            return Err(format!("no .{EXPORT_EXTENSION} exports in {}", input.display()).into());
+        }
+        return Ok(files);
+    }
+
+    let pattern = input.to_string_lossy();
+    if input.exists() || !pattern.contains(GLOB_CHARS) {
+        return Ok(vec![input.to_path_buf()]);
+    }
+
+    let files = glob::glob(&pattern)?
+        .filter_map(|p| p.ok())
+        .filter(|p| p.is_file())
+        .collect::<Vec<_>>();
+    if files.is_empty() {
+        return Err(format!("no files match {pattern}").into());
+    }
+    Ok(files)
+}
+
+/// Fills in `{title}`, `{author}`, `{stem}` (the input file name without its extension) and
+/// `{ext}` in a file name template like `{author} - {title}.md`, and makes the result a safe
+/// relative path, transliterated to ASCII with `ascii`.
+/// Slashes in the values are replaced, while slashes in the template create subdirectories.
+pub fn file_name(
+    template: &str,
+    boox: &BooxFile,
+    input_file: &Path,
+    extension: &str,
+    ascii: bool,
+) -> PathBuf {
+    let author = match boox.metadata.author_names() {
+        names if names.is_empty() => UNKNOWN_AUTHOR.to_owned(),
+        names => names,
+    };
+    let stem = input_file
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+
+    let name = template
+        .replace("{title}", &path_safe(&boox.metadata.title))
+        .replace("{author}", &path_safe(&author))
+        .replace("{stem}", &path_safe(&stem))
+        .replace("{ext}", extension);
+    safe_path(&name, ascii)
+}
+
+fn path_safe(s: &str) -> String {
+    s.replace(['/', '\\'], "-").replace(char::is_control, "")
+}
+
+#[test]
+fn expand_input_test() {
+    let files = expand_input(Path::new("test/data")).unwrap();
+    assert_eq!(files.len(), 5);
+    assert_eq!(files[0], Path::new("test/data/Harari_Eine-kurze-Geschichte-der-Menschheit-annotation-2023-02-21_17_11_42.txt"));
+
+    let files = expand_input(Path::new("test/data/*1.txt")).unwrap();
+    assert_eq!(
+        files,
+        vec![
+            PathBuf::from("test/data/russian_1.txt"),
+            PathBuf::from("test/data/v1.txt")
+        ]
+    );
+
+    assert_eq!(
+        expand_input(Path::new("missing.txt")).unwrap(),
+        vec![PathBuf::from("missing.txt")]
+    );
+    assert!(expand_input(Path::new("test/data/*.pdf")).is_err());
+}
+
+#[test]
+fn file_name_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/v1.txt")).unwrap();
+    let input_file = Path::new("exports/v1.txt");
+
+    assert_eq!(
+        file_name(DEFAULT_FILE_NAME, &boox, input_file, "md", false),
+        Path::new("Peter Lynch, John Rothchild - One Up on Wall Street.md")
+    );
+    assert_eq!(
+        file_name("{author}/{stem}.json", &boox, input_file, "md", false),
+        Path::new("Peter Lynch, John Rothchild/v1.json")
+    );
+    assert_eq!(
+        file_name("../{title}.{ext}", &boox, input_file, "md", false),
+        Path::new("One Up on Wall Street.md")
+    );
+
+    let (_, mut boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    boox.metadata.title = "Either/Or".to_owned();
+    boox.metadata.authors.clear();
+    assert_eq!(
+        file_name(DEFAULT_FILE_NAME, &boox, input_file, "md", false),
+        Path::new("Unknown - Either-Or.md")
+    );
+
+    let (_, boox) = parse_boox(include_str!("../test/data/russian_1.txt")).unwrap();
+    assert_eq!(
+        file_name(DEFAULT_FILE_NAME, &boox, input_file, "md", true),
+        Path::new("Robert Sesil Martin - Chistaia arkhitektura.md")
+    );
+}
@@ -0,0 +1,113 @@
+//! # `binary_cache`
+//!
+//! Compact CBOR and MessagePack encodings of a parsed `BooxFile`, meant for caching libraries.
+//! Every file starts with a header carrying the encoding and the schema version of the payload,
+//! so that caches written by an incompatible version are rejected instead of misread.
+
+use std::error::Error;
+
+use crate::{
+    json_schema::{JsonBook, SCHEMA_VERSION},
+    model::BooxFile,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+const MAGIC: &[u8; 4] = b"HLX\0";
+const HEADER_LEN: usize = MAGIC.len() + 1 + 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryFormat {
+    Cbor,
+    MessagePack,
+}
+
+impl BinaryFormat {
+    const fn tag(self) -> u8 {
+        match self {
+            Self::Cbor => b'c',
+            Self::MessagePack => b'm',
+        }
+    }
+
+    const fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            b'c' => Some(Self::Cbor),
+            b'm' => Some(Self::MessagePack),
+            _ => None,
+        }
+    }
+}
+
+pub fn is_binary_cache(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+pub fn to_binary(boox: &BooxFile, format: BinaryFormat) -> Result<Vec<u8>> {
+    let book = JsonBook::from(boox);
+    let version = u32::try_from(SCHEMA_VERSION)?;
+
+    let mut out = Vec::from(*MAGIC);
+    out.push(format.tag());
+    out.extend_from_slice(&version.to_be_bytes());
+
+    match format {
+        BinaryFormat::Cbor => ciborium::into_writer(&book, &mut out)?,
+        // Named fields keep optional fields skippable, which positional arrays cannot do
+        BinaryFormat::MessagePack => out.extend(rmp_serde::to_vec_named(&book)?),
+    }
+
+    Ok(out)
+}
+
+pub fn from_binary(data: &[u8]) -> Result<BooxFile> {
+    if !is_binary_cache(data) || data.len() < HEADER_LEN {
+        return Err("not a highlight-extract cache file".into());
+    }
+
+    let format = BinaryFormat::from_tag(data[MAGIC.len()])
+        .ok_or_else(|| format!("unknown cache encoding {:?}", data[MAGIC.len()] as char))?;
+    let version = u32::from_be_bytes(data[MAGIC.len() + 1..HEADER_LEN].try_into()?);
+
+    if u64::from(version) != SCHEMA_VERSION {
+        return Err(format!(
+            "stale cache with schema version {version}, expected {SCHEMA_VERSION}; parse the export again"
+        )
+        .into());
+    }
+
+    let payload = &data[HEADER_LEN..];
+    let book: JsonBook = match format {
+        BinaryFormat::Cbor => ciborium::from_reader(payload)?,
+        BinaryFormat::MessagePack => rmp_serde::from_slice(payload)?,
+    };
+
+    Ok(book.into())
+}
+
+#[test]
+fn roundtrip_test() {
+    use crate::parse_boox::parse_boox;
+
+    for format in [BinaryFormat::Cbor, BinaryFormat::MessagePack] {
+        let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+        let data = to_binary(&boox, format).unwrap();
+
+        assert!(is_binary_cache(&data));
+        assert_eq!(from_binary(&data).unwrap(), boox);
+    }
+}
+
+#[test]
+fn stale_cache_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/v1.txt")).unwrap();
+    let mut data = to_binary(&boox, BinaryFormat::Cbor).unwrap();
+    data[MAGIC.len() + 4] += 1;
+
+    let err = from_binary(&data).unwrap_err();
+    assert!(err.to_string().starts_with("stale cache"));
+
+    assert!(from_binary(b"Reading Notes").is_err());
+}
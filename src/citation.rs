@@ -0,0 +1,225 @@
+//! # `citation`
+//!
+//! Bibliography entries for a book in BibTeX and CSL-JSON, and the citation key that the
+//! Markdown output uses for Pandoc citations like `[@lynch1989, p. 13]`
+
+use deunicode::deunicode;
+use serde_json::{json, Value};
+
+use crate::{
+    display_latex::escape_latex,
+    model::{Author, Metadata},
+};
+
+/// Words skipped when a title word goes into a citation key
+const STOP_WORDS: &[&str] = &["a", "an", "the", "der", "die", "das", "ein", "eine"];
+
+/// Publication details that exports don't contain
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Publication {
+    pub isbn: Option<String>,
+    pub publisher: Option<String>,
+    pub year: Option<i32>,
+}
+
+/// A key like `lynch1989` from the first author's last name and the year, or the first word of
+/// the title when the year isn't known. Books without authors start with a title word instead.
+/// Keys are transliterated to ASCII, which is all that BibTeX accepts.
+pub fn citation_key(metadata: &Metadata, publication: &Publication) -> String {
+    let title = deunicode(&metadata.title);
+    let mut title_words = title
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|w| !w.is_empty() && !STOP_WORDS.contains(&w.as_str()));
+
+    let name = metadata
+        .authors
+        .first()
+        .map(|a| {
+            key_part(if a.last_name.is_empty() {
+                &a.first_name
+            } else {
+                &a.last_name
+            })
+        })
+        .filter(|n| !n.is_empty())
+        .or_else(|| title_words.next());
+    let suffix = publication
+        .year
+        .map(|y| y.to_string())
+        .or_else(|| title_words.next());
+
+    let key: String = name.into_iter().chain(suffix).collect();
+    if key.is_empty() {
+        "book".to_owned()
+    } else {
+        key
+    }
+}
+
+fn key_part(s: &str) -> String {
+    deunicode(s)
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// A Pandoc citation of a page of the book
+pub fn pandoc_citation(key: &str, page: u32) -> String {
+    format!("[@{key}, p. {page}]")
+}
+
+pub fn to_bibtex(metadata: &Metadata, publication: &Publication) -> String {
+    let mut fields = vec![("title", format!("{{{}}}", escape_latex(&metadata.title)))];
+    if !metadata.authors.is_empty() {
+        let authors: Vec<String> = metadata.authors.iter().map(bibtex_name).collect();
+        fields.push(("author", escape_latex(&authors.join(" and "))));
+    }
+    if let Some(year) = publication.year {
+        fields.push(("year", year.to_string()));
+    }
+    if let Some(publisher) = &publication.publisher {
+        fields.push(("publisher", escape_latex(publisher)));
+    }
+    if let Some(isbn) = &publication.isbn {
+        fields.push(("isbn", escape_latex(isbn)));
+    }
+
+    let fields: String = fields
+        .iter()
+        .map(|(name, value)| format!("  {name} = {{{value}}},\n"))
+        .collect();
+
+    format!(
+        "@book{{{},\n{fields}}}\n",
+        citation_key(metadata, publication)
+    )
+}
+
+// "Last, First" keeps multi-word first names apart from the last name
+fn bibtex_name(author: &Author) -> String {
+    match (author.first_name.is_empty(), author.last_name.is_empty()) {
+        (false, false) => format!("{}, {}", author.last_name, author.first_name),
+        _ => author.to_string(),
+    }
+}
+
+pub fn to_csl_json(metadata: &Metadata, publication: &Publication) -> Value {
+    let mut item = json!({
+        "id": citation_key(metadata, publication),
+        "type": "book",
+        "title": metadata.title,
+    });
+
+    if !metadata.authors.is_empty() {
+        let authors: Vec<Value> = metadata
+            .authors
+            .iter()
+            .map(
+                |a| match (a.first_name.is_empty(), a.last_name.is_empty()) {
+                    (false, false) => json!({ "family": a.last_name, "given": a.first_name }),
+                    _ => json!({ "literal": a.to_string() }),
+                },
+            )
+            .collect();
+        item["author"] = json!(authors);
+    }
+    if let Some(year) = publication.year {
+        item["issued"] = json!({ "date-parts": [[year]] });
+    }
+    if let Some(publisher) = &publication.publisher {
+        item["publisher"] = json!(publisher);
+    }
+    if let Some(isbn) = &publication.isbn {
+        item["ISBN"] = json!(isbn);
+    }
+
+    json!([item])
+}
+
+#[test]
+fn citation_key_test() {
+    let lynch = Metadata::from_raw("One Up on Wall Street", "Peter Lynch; John Rothchild");
+    let year = Publication {
+        year: Some(1989),
+        ..Publication::default()
+    };
+
+    assert_eq!(citation_key(&lynch, &year), "lynch1989");
+    assert_eq!(citation_key(&lynch, &Publication::default()), "lynchone");
+    assert_eq!(
+        citation_key(&Metadata::from_raw("The Art of War", ""), &year),
+        "art1989"
+    );
+    assert_eq!(
+        citation_key(
+            &Metadata::from_raw("The Art of War", ""),
+            &Publication::default()
+        ),
+        "artof"
+    );
+    assert_eq!(
+        citation_key(
+            &Metadata::from_raw("Чистая архитектура", "Роберт Мартин"),
+            &year
+        ),
+        "martin1989"
+    );
+    assert_eq!(
+        citation_key(
+            &Metadata::from_raw("Чистая архитектура", ""),
+            &Publication::default()
+        ),
+        "chistaiaarkhitektura"
+    );
+    assert_eq!(
+        citation_key(
+            &Metadata::from_raw("Über formal unentscheidbare Sätze", "Kurt Gödel"),
+            &year
+        ),
+        "godel1989"
+    );
+    assert_eq!(
+        citation_key(&Metadata::from_raw("", ""), &Publication::default()),
+        "book"
+    );
+}
+
+#[test]
+fn bibliography_test() {
+    let metadata = Metadata::from_raw("One Up on Wall Street", "Peter Lynch; John Rothchild");
+    let publication = Publication {
+        isbn: Some("978-0-7432-0040-3".to_owned()),
+        publisher: Some("Simon & Schuster".to_owned()),
+        year: Some(1989),
+    };
+
+    assert_eq!(
+        to_bibtex(&metadata, &publication),
+        "@book{lynch1989,
+  title = {{One Up on Wall Street}},
+  author = {Lynch, Peter and Rothchild, John},
+  year = {1989},
+  publisher = {Simon \\& Schuster},
+  isbn = {978-0-7432-0040-3},
+}
+"
+    );
+
+    assert_eq!(
+        to_csl_json(&metadata, &publication),
+        json!([{
+            "id": "lynch1989",
+            "type": "book",
+            "title": "One Up on Wall Street",
+            "author": [
+                { "family": "Lynch", "given": "Peter" },
+                { "family": "Rothchild", "given": "John" }
+            ],
+            "issued": { "date-parts": [[1989]] },
+            "publisher": "Simon & Schuster",
+            "ISBN": "978-0-7432-0040-3"
+        }])
+    );
+}
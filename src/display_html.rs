@@ -0,0 +1,180 @@
+//! # `display_html`
+//!
+//! Self-contained HTML page with embedded styles, a table of contents built from the chapters
+//! and the language of the export in `lang`/`dir`, for sharing with people without Markdown tools
+
+use std::fmt::{Display, Formatter, Result};
+
+use crate::{
+    model::{BooxFile, Highlight, Section},
+    slug::slugify,
+    text::escape_html,
+};
+
+const STYLE: &str = include_str!("../templates/html.css");
+
+/// Languages written right to left
+const RTL_LANGUAGES: &[&str] = &["ar", "fa", "he", "ps", "ur", "yi"];
+
+pub struct Html<'a>(pub &'a BooxFile);
+
+impl Display for Html<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let boox = self.0;
+        let title = escape_html(&boox.metadata.title);
+        let anchors = chapter_anchors(boox);
+
+        let locale = boox.provenance.as_ref().and_then(|p| p.locale.as_deref());
+        match locale {
+            Some(locale) => {
+                let language = locale.split(['-', '_']).next().unwrap_or(locale);
+                let dir = if RTL_LANGUAGES.contains(&language) {
+                    "rtl"
+                } else {
+                    "ltr"
+                };
+                writeln!(
+                    f,
+                    "<!DOCTYPE html>\n<html lang=\"{}\" dir=\"{dir}\">",
+                    escape_html(locale)
+                )?;
+            }
+            None => writeln!(f, "<!DOCTYPE html>\n<html>")?,
+        }
+        writeln!(f, "<head>")?;
+        writeln!(f, "<meta charset=\"utf-8\">")?;
+        writeln!(
+            f,
+            "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">"
+        )?;
+        writeln!(f, "<title>{title}</title>")?;
+        writeln!(f, "<style>\n{STYLE}</style>")?;
+        writeln!(f, "</head>")?;
+        writeln!(f, "<body>")?;
+
+        writeln!(f, "<header>")?;
+        writeln!(f, "<h1>{title}</h1>")?;
+        if !boox.metadata.authors.is_empty() {
+            writeln!(
+                f,
+                "<p class=\"author\">{}</p>",
+                escape_html(&boox.metadata.author_names())
+            )?;
+        }
+        writeln!(f, "</header>")?;
+
+        let chapters = boox.sections.iter().filter_map(|s| match s {
+            Section::Chapter(c) => Some(c),
+            Section::HL(_) => None,
+        });
+        if !anchors.is_empty() {
+            writeln!(f, "<nav class=\"toc\">\n<h2>Contents</h2>\n<ol>")?;
+            for (chapter, anchor) in chapters.zip(&anchors) {
+                writeln!(
+                    f,
+                    "<li><a href=\"#{anchor}\">{}</a></li>",
+                    escape_html(&chapter.title)
+                )?;
+            }
+            writeln!(f, "</ol>\n</nav>")?;
+        }
+
+        writeln!(f, "<main>")?;
+        let mut anchors = anchors.iter();
+        for section in &boox.sections {
+            match section {
+                Section::Chapter(c) => writeln!(
+                    f,
+                    "<h2 class=\"chapter\" id=\"{}\">{}</h2>",
+                    anchors.next().expect("one anchor per chapter"),
+                    escape_html(&c.title)
+                )?,
+                Section::HL(highlight) => write_highlight(f, highlight)?,
+            }
+        }
+        writeln!(f, "</main>")?;
+
+        writeln!(f, "</body>\n</html>")
+    }
+}
+
+fn write_highlight(f: &mut Formatter, highlight: &Highlight) -> Result {
+    writeln!(f, "<figure class=\"highlight\" id=\"h-{}\">", highlight.id)?;
+    writeln!(
+        f,
+        "<blockquote>{}</blockquote>",
+        escape_html(highlight.highlight.trim_end())
+    )?;
+    writeln!(
+        f,
+        "<figcaption>Page {} · <time datetime=\"{}\">{}</time></figcaption>",
+        highlight.page,
+        highlight.timestamp.format("%Y-%m-%dT%H:%M:%S"),
+        highlight.timestamp.format("%Y-%m-%d %H:%M")
+    )?;
+    writeln!(f, "</figure>")?;
+
+    if let Some(note) = &highlight.note {
+        writeln!(
+            f,
+            "<aside class=\"note\">{}</aside>",
+            escape_html(note.trim_end())
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Unique anchors for the chapters in order, numbered if titles repeat or have no usable characters
+fn chapter_anchors(boox: &BooxFile) -> Vec<String> {
+    let mut anchors: Vec<String> = vec![];
+
+    for section in &boox.sections {
+        let Section::Chapter(chapter) = section else {
+            continue;
+        };
+
+        let slug = match slugify(&chapter.title) {
+            s if s.is_empty() => "chapter".to_owned(),
+            s => s,
+        };
+        let mut anchor = slug.clone();
+        let mut n = 1;
+        while anchors.contains(&anchor) {
+            n += 1;
+            anchor = format!("{slug}-{n}");
+        }
+        anchors.push(anchor);
+    }
+
+    anchors
+}
+
+#[test]
+fn html_test() {
+    use crate::{model::Provenance, parse_boox::parse_boox};
+
+    let data = include_str!("../test/data/data.txt");
+    let (_, mut boox) = parse_boox(data).unwrap();
+    boox.provenance = Some(Provenance::new(None, data));
+    let html = Html(&boox).to_string();
+
+    assert!(html.starts_with("<!DOCTYPE html>\n<html lang=\"en\" dir=\"ltr\">\n"));
+    assert!(html.contains(
+        "<nav class=\"toc\">
+<h2>Contents</h2>
+<ol>
+<li><a href=\"#chapter-3-how-a-second-brain-works\">Chapter 3: How a Second Brain Works</a></li>
+</ol>
+</nav>"
+    ));
+    assert!(html.contains(
+        "<h2 class=\"chapter\" id=\"chapter-3-how-a-second-brain-works\">Chapter 3: How a Second Brain Works</h2>
+<figure class=\"highlight\" id=\"h-93d7c1a793812574\">
+<blockquote>We bookmark articles to read later, but rarely find the time to revisit them again</blockquote>
+<figcaption>Page 32 · <time datetime=\"2023-04-03T01:21:00\">2023-04-03 01:21</time></figcaption>
+</figure>
+<aside class=\"note\">There&#39;s too many to \nactually read them all</aside>"
+    ));
+    assert!(html.ends_with("</main>\n</body>\n</html>\n"));
+}
@@ -0,0 +1,171 @@
+//! # `display_latex`
+//!
+//! LaTeX source for printed summaries: a section per chapter, highlights in `quote`
+//! environments with their page, and notes as footnotes. Books with CJK text get an
+//! `xeCJK` preamble that needs XeLaTeX, everything else compiles with pdfLaTeX.
+
+use std::fmt::{Display, Formatter, Result};
+
+use crate::{
+    model::{BooxFile, Highlight, Section},
+    script::Scripts,
+    text::normalise_whitespace,
+};
+
+pub struct Latex<'a>(pub &'a BooxFile);
+
+impl Display for Latex<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let boox = self.0;
+        let scripts = Scripts::of(boox);
+
+        writeln!(f, "\\documentclass[a4paper]{{article}}")?;
+        if scripts.cjk {
+            writeln!(f, "% Contains CJK text, compile with xelatex")?;
+            writeln!(f, "\\usepackage{{fontspec}}")?;
+            writeln!(f, "\\usepackage{{xeCJK}}")?;
+            writeln!(f, "% \\setCJKmainfont{{Noto Serif CJK SC}}")?;
+        } else {
+            writeln!(f, "\\usepackage[utf8]{{inputenc}}")?;
+            if scripts.cyrillic {
+                writeln!(f, "\\usepackage[T2A,T1]{{fontenc}}")?;
+            } else {
+                writeln!(f, "\\usepackage[T1]{{fontenc}}")?;
+            }
+        }
+
+        let language = babel_language(boox, scripts);
+        if scripts.cyrillic && language != "russian" {
+            writeln!(f, "\\usepackage[russian,{language}]{{babel}}")?;
+        } else {
+            writeln!(f, "\\usepackage[{language}]{{babel}}")?;
+        }
+        writeln!(f)?;
+
+        writeln!(f, "\\title{{{}}}", escape_latex(&boox.metadata.title))?;
+        let authors: Vec<String> = boox
+            .metadata
+            .authors
+            .iter()
+            .map(|a| escape_latex(&a.to_string()))
+            .collect();
+        writeln!(f, "\\author{{{}}}", authors.join(" \\and "))?;
+        writeln!(f, "\\date{{}}")?;
+        writeln!(f)?;
+        writeln!(f, "\\begin{{document}}")?;
+        writeln!(f, "\\maketitle")?;
+
+        for section in &boox.sections {
+            match section {
+                Section::Chapter(c) => {
+                    writeln!(
+                        f,
+                        "\n\\section{{{}}}",
+                        escape_latex(&normalise_whitespace(&c.title))
+                    )?;
+                }
+                Section::HL(highlight) => write_highlight(f, highlight)?,
+            }
+        }
+
+        writeln!(f, "\n\\end{{document}}")
+    }
+}
+
+fn write_highlight(f: &mut Formatter, highlight: &Highlight) -> Result {
+    writeln!(f, "\n\\begin{{quote}}")?;
+    writeln!(f, "{}", paragraphs(&highlight.highlight))?;
+    write!(f, "\\par\\hfill p.~{}", highlight.page)?;
+    if let Some(note) = &highlight.note {
+        write!(f, "\\footnote{{{}}}", paragraphs(note))?;
+    }
+    writeln!(f, "\n\\end{{quote}}")
+}
+
+/// The babel name of the export's language, or a guess from the script
+fn babel_language(boox: &BooxFile, scripts: Scripts) -> &'static str {
+    let locale = boox.provenance.as_ref().and_then(|p| p.locale.as_deref());
+
+    match locale {
+        Some("de") => "ngerman",
+        Some("ru") => "russian",
+        Some(_) => "english",
+        None if scripts.cyrillic => "russian",
+        None => "english",
+    }
+}
+
+/// Escaped text with every line as its own paragraph
+fn paragraphs(s: &str) -> String {
+    s.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(escape_latex)
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+pub fn escape_latex(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\textbackslash{}"),
+            '~' => out.push_str("\\textasciitilde{}"),
+            '^' => out.push_str("\\textasciicircum{}"),
+            // An active shorthand character with ngerman babel
+            '"' => out.push_str("\\textquotedbl{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[test]
+fn escape_latex_test() {
+    assert_eq!(
+        escape_latex(r"50% of $10 & #1_{a}~^\"),
+        r"50\% of \$10 \& \#1\_\{a\}\textasciitilde{}\textasciicircum{}\textbackslash{}"
+    );
+    assert_eq!(
+        escape_latex(r#"Er sagte "a" und "`b""#),
+        r"Er sagte \textquotedbl{}a\textquotedbl{} und \textquotedbl{}`b\textquotedbl{}"
+    );
+}
+
+#[test]
+fn latex_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    let latex = Latex(&boox).to_string();
+
+    assert!(latex.starts_with(
+        "\\documentclass[a4paper]{article}
+\\usepackage[utf8]{inputenc}
+\\usepackage[T1]{fontenc}
+\\usepackage[english]{babel}
+
+\\title{Building a Second Brain -- A Proven Method}
+\\author{Tiago Forte}
+"
+    ));
+    assert!(latex.contains(
+        "\\section{Chapter 3: How a Second Brain Works}
+
+\\begin{quote}
+We bookmark articles to read later, but rarely find the time to revisit them again
+\\par\\hfill p.~32\\footnote{There's too many to
+
+actually read them all}
+\\end{quote}
+"
+    ));
+
+    let (_, boox) = parse_boox(include_str!("../test/data/russian_1.txt")).unwrap();
+    let latex = Latex(&boox).to_string();
+    assert!(latex.contains("\\usepackage[T2A,T1]{fontenc}\n\\usepackage[russian]{babel}\n"));
+}
@@ -0,0 +1,130 @@
+//! # `display_logseq`
+//!
+//! Logseq outline: page properties for the book, chapters as parent blocks and highlights as
+//! child blocks with `page::` and `date::` properties, notes nested below their highlight
+
+use crate::{
+    display_markdown::AsMarkdown,
+    model::{BooxFile, Highlight, Section},
+    text::{extract_tags, normalise_whitespace},
+};
+
+pub struct Logseq<'a>(pub &'a BooxFile);
+
+impl AsMarkdown for Logseq<'_> {
+    fn fmt_markdown(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let boox = self.0;
+        let notes = boox.sections.iter().filter_map(|s| match s {
+            Section::HL(h) => h.note.as_deref(),
+            Section::Chapter(_) => None,
+        });
+        let tags: Vec<String> = ["book".to_owned()]
+            .into_iter()
+            .chain(extract_tags(notes))
+            .collect();
+
+        writeln!(f, "title:: {}", normalise_whitespace(&boox.metadata.title))?;
+        let authors: Vec<String> = boox
+            .metadata
+            .authors
+            .iter()
+            .map(|a| format!("[[{a}]]"))
+            .collect();
+        if !authors.is_empty() {
+            writeln!(f, "author:: {}", authors.join(", "))?;
+        }
+        if let Some(path) = boox
+            .provenance
+            .as_ref()
+            .and_then(|p| p.source_path.as_ref())
+        {
+            let name = path.file_name().unwrap_or(path.as_os_str());
+            writeln!(f, "source:: {}", name.to_string_lossy())?;
+        }
+        writeln!(f, "tags:: {}", tags.join(", "))?;
+        writeln!(f)?;
+
+        let mut depth = 0;
+        for section in &boox.sections {
+            match section {
+                Section::Chapter(c) => {
+                    write_block(f, 0, &c.title, &[])?;
+                    depth = 1;
+                }
+                Section::HL(highlight) => write_highlight(f, depth, highlight)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_highlight(
+    f: &mut std::fmt::Formatter,
+    depth: usize,
+    highlight: &Highlight,
+) -> std::fmt::Result {
+    let page = highlight.page.to_string();
+    let date = highlight.timestamp.format("%Y-%m-%d %H:%M").to_string();
+    let properties = [("page", page.as_str()), ("date", date.as_str())];
+
+    write_block(f, depth, &highlight.highlight, &properties)?;
+
+    if let Some(note) = &highlight.note {
+        write_block(f, depth + 1, note, &[])?;
+    }
+
+    Ok(())
+}
+
+// Logseq expects block properties directly after the first line of the block
+fn write_block(
+    f: &mut std::fmt::Formatter,
+    depth: usize,
+    text: &str,
+    properties: &[(&str, &str)],
+) -> std::fmt::Result {
+    let indent = "\t".repeat(depth);
+    let mut lines = text.lines();
+
+    writeln!(f, "{indent}- {}", lines.next().unwrap_or_default())?;
+    for (key, value) in properties {
+        writeln!(f, "{indent}  {key}:: {value}")?;
+    }
+    for line in lines {
+        writeln!(f, "{indent}  {line}")?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn logseq_test() {
+    use crate::{display_markdown::DisplayMarkdown, parse_boox::parse_boox};
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+
+    assert_eq!(
+        DisplayMarkdown(&Logseq(&boox)).to_string(),
+        "title:: Building a Second Brain -- A Proven Method
+author:: [[Tiago Forte]]
+tags:: book
+
+- PKM—or personal knowledge management
+  page:: 6
+  date:: 2023-04-03 00:41
+- Chapter 3: How a Second Brain Works
+\t- We bookmark articles to read later, but rarely find the time to revisit them again
+\t  page:: 32
+\t  date:: 2023-04-03 01:21
+\t\t- There's too many to \n\t\t  actually read them all
+\t- In other words, \n\t  page:: 39
+\t  date:: 2023-04-03 16:57
+\t  the jobs that are most likely to stick around are those that involve promoting or defending a particular perspective
+\t\t- Not sure about now with LLMs
+\t- Multimedia
+\t  page:: 40
+\t  date:: 2023-04-03 17:01
+"
+    );
+}
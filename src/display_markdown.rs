@@ -1,6 +1,9 @@
 use std::fmt::Display;
 
-use crate::model::{BooxFile, Section};
+use crate::{
+    citation::pandoc_citation,
+    model::{BooxFile, Section},
+};
 
 pub struct DisplayMarkdown<'a, T: AsMarkdown>(pub &'a T);
 
@@ -14,18 +17,43 @@ pub trait AsMarkdown {
     fn fmt_markdown(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result;
 }
 
+/// Quotes a string for use as a scalar in YAML front matter
+pub fn yaml_string(s: &str) -> Result<String, std::fmt::Error> {
+    // JSON strings are valid YAML scalars and take care of escaping
+    serde_json::to_string(s).map_err(|_| std::fmt::Error)
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MarkdownOptions<'a> {
+    /// Add a `^id` block anchor after each highlight so it can be linked to
+    pub block_ids: bool,
+    /// End each highlight with a Pandoc citation of its page under this key
+    pub citation_key: Option<&'a str>,
+}
+
+/// A `BooxFile` rendered with non-default `MarkdownOptions`
+pub struct BooxMarkdown<'a>(pub &'a BooxFile, pub MarkdownOptions<'a>);
+
 impl AsMarkdown for BooxFile {
     fn fmt_markdown(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        writeln!(f, "# {}", &self.metadata.title)?;
+        BooxMarkdown(self, MarkdownOptions::default()).fmt_markdown(f)
+    }
+}
+
+impl AsMarkdown for BooxMarkdown<'_> {
+    fn fmt_markdown(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let BooxMarkdown(boox, options) = self;
+
+        writeln!(f, "# {}", &boox.metadata.title)?;
         writeln!(f)?;
-        writeln!(f, "**Author:** {}", &self.metadata.author)?;
+        writeln!(f, "**Author:** {}", boox.metadata.author_names())?;
         writeln!(f, "\n---\n")?;
 
         writeln!(f, "## Highlights\n")?;
 
-        for section in &self.sections {
+        for section in &boox.sections {
             match section {
-                Section::Chapter(c) => writeln!(f, "### {c}\n")?,
+                Section::Chapter(c) => writeln!(f, "### {}\n", c.title)?,
                 Section::HL(highlight) => {
                     writeln!(
                         f,
@@ -35,8 +63,15 @@ impl AsMarkdown for BooxFile {
                     for line in highlight.highlight.lines() {
                         writeln!(f, "> {line}")?;
                     }
+                    if let Some(key) = options.citation_key {
+                        writeln!(f, "> {}", pandoc_citation(key, highlight.page))?;
+                    }
                     writeln!(f)?;
 
+                    if options.block_ids {
+                        writeln!(f, "^{}\n", highlight.id)?;
+                    }
+
                     if let Some(n) = &highlight.note {
                         writeln!(f, "{n}\n")?;
                     }
@@ -47,3 +82,31 @@ impl AsMarkdown for BooxFile {
         Ok(())
     }
 }
+
+#[test]
+fn block_id_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    let options = MarkdownOptions {
+        block_ids: true,
+        ..MarkdownOptions::default()
+    };
+    let md = DisplayMarkdown(&BooxMarkdown(&boox, options)).to_string();
+
+    assert!(md.contains("> PKM—or personal knowledge management\n\n^30aa3585d76d8ebd\n\n"));
+}
+
+#[test]
+fn citation_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/v1.txt")).unwrap();
+    let options = MarkdownOptions {
+        citation_key: Some("lynch1989"),
+        ..MarkdownOptions::default()
+    };
+    let md = DisplayMarkdown(&BooxMarkdown(&boox, options)).to_string();
+
+    assert!(md.contains("> valued at $10 billion may not be worth a dime. As expectations turn to reality, the winners will be more obvious than they are today. Investors who see this will have time to act on their “edge.”\n> [@lynch1989, p. 20]\n\n"));
+}
@@ -0,0 +1,134 @@
+//! # `display_obsidian`
+//!
+//! Markdown for Obsidian vaults: YAML front matter, `^id` block anchors, note callouts,
+//! `[[author]]` links and the `#tags` used in notes
+
+use crate::{
+    display_markdown::{yaml_string, AsMarkdown},
+    model::{BooxFile, Highlight, Section},
+    text::extract_tags,
+};
+
+pub struct Obsidian<'a>(pub &'a BooxFile);
+
+impl AsMarkdown for Obsidian<'_> {
+    fn fmt_markdown(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let boox = self.0;
+        let highlights: Vec<&Highlight> = boox
+            .sections
+            .iter()
+            .filter_map(|s| match s {
+                Section::HL(h) => Some(h),
+                Section::Chapter(_) => None,
+            })
+            .collect();
+        let tags = extract_tags(highlights.iter().filter_map(|h| h.note.as_deref()));
+
+        writeln!(f, "---")?;
+        writeln!(f, "title: {}", yaml_string(&boox.metadata.title)?)?;
+        if boox.metadata.authors.is_empty() {
+            writeln!(f, "authors: []")?;
+        } else {
+            writeln!(f, "authors:")?;
+            for author in &boox.metadata.authors {
+                writeln!(f, "  - {}", yaml_string(&format!("[[{author}]]"))?)?;
+            }
+        }
+        if tags.is_empty() {
+            writeln!(f, "tags: []")?;
+        } else {
+            writeln!(f, "tags:")?;
+            for tag in &tags {
+                writeln!(f, "  - {}", yaml_string(tag)?)?;
+            }
+        }
+        writeln!(f, "highlights: {}", highlights.len())?;
+        if let Some(first) = highlights.iter().map(|h| h.timestamp).min() {
+            writeln!(f, "first_highlight: {}", first.format("%Y-%m-%d"))?;
+        }
+        if let Some(last) = highlights.iter().map(|h| h.timestamp).max() {
+            writeln!(f, "last_highlight: {}", last.format("%Y-%m-%d"))?;
+        }
+        if let Some(exported_at) = boox.provenance.as_ref().and_then(|p| p.exported_at) {
+            writeln!(f, "exported: {}", exported_at.format("%Y-%m-%d"))?;
+        }
+        writeln!(f, "---\n")?;
+
+        writeln!(f, "# {}\n", &boox.metadata.title)?;
+        let authors: Vec<String> = boox
+            .metadata
+            .authors
+            .iter()
+            .map(|a| format!("[[{a}]]"))
+            .collect();
+        writeln!(f, "**Author:** {}\n", authors.join(", "))?;
+
+        writeln!(f, "## Highlights\n")?;
+
+        for section in &boox.sections {
+            match section {
+                Section::Chapter(c) => writeln!(f, "### {}\n", c.title)?,
+                Section::HL(highlight) => {
+                    writeln!(
+                        f,
+                        "#### Page {} ({})\n",
+                        highlight.page,
+                        highlight.timestamp.format("%Y-%m-%d %H:%M")
+                    )?;
+                    for line in highlight.highlight.lines() {
+                        writeln!(f, "> {line}")?;
+                    }
+                    writeln!(f, "\n^{}\n", highlight.id)?;
+
+                    if let Some(note) = &highlight.note {
+                        writeln!(f, "> [!note]")?;
+                        for line in note.lines() {
+                            writeln!(f, "> {line}")?;
+                        }
+                        writeln!(f)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn obsidian_test() {
+    use crate::{display_markdown::DisplayMarkdown, parse_boox::parse_boox};
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    let md = DisplayMarkdown(&Obsidian(&boox)).to_string();
+
+    assert!(md.starts_with(
+        "---
+title: \"Building a Second Brain -- A Proven Method\"
+authors:
+  - \"[[Tiago Forte]]\"
+tags: []
+highlights: 4
+first_highlight: 2023-04-03
+last_highlight: 2023-04-03
+---
+
+# Building a Second Brain -- A Proven Method
+
+**Author:** [[Tiago Forte]]
+
+## Highlights
+
+#### Page 6 (2023-04-03 00:41)
+
+> PKM—or personal knowledge management
+
+^30aa3585d76d8ebd
+
+### Chapter 3: How a Second Brain Works
+"
+    ));
+    assert!(md.contains(
+        "^93d7c1a793812574\n\n> [!note]\n> There's too many to \n> actually read them all\n\n"
+    ));
+}
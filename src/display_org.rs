@@ -0,0 +1,184 @@
+//! # `display_org`
+//!
+//! Emacs Org-mode document: chapters as headlines, highlights as quote blocks under their own
+//! headlines with a property drawer. The book and every highlight carry an `:ID:` property, so
+//! org-roam picks them up as nodes that can be linked with `[[id:...]]`.
+
+use std::fmt::{Display, Formatter, Result};
+
+use crate::{
+    highlight_id::book_id,
+    model::{BooxFile, Highlight, Section},
+    text::normalise_whitespace,
+};
+
+/// Words of the highlight shown in its headline
+const HEADLINE_WORDS: usize = 8;
+
+pub struct Org<'a>(pub &'a BooxFile);
+
+impl Display for Org<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let boox = self.0;
+
+        writeln!(f, ":PROPERTIES:")?;
+        writeln!(f, ":ID:       {}", book_id(&boox.metadata))?;
+        writeln!(f, ":END:")?;
+        writeln!(f, "#+TITLE: {}", normalise_whitespace(&boox.metadata.title))?;
+        if !boox.metadata.authors.is_empty() {
+            writeln!(f, "#+AUTHOR: {}", boox.metadata.author_names())?;
+        }
+        if let Some(locale) = boox.provenance.as_ref().and_then(|p| p.locale.as_ref()) {
+            writeln!(f, "#+LANGUAGE: {locale}")?;
+        }
+
+        let mut level = 1;
+        for section in &boox.sections {
+            match section {
+                Section::Chapter(c) => {
+                    writeln!(f, "\n* {}", normalise_whitespace(&c.title))?;
+                    level = 2;
+                }
+                Section::HL(highlight) => write_highlight(f, level, highlight)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_highlight(f: &mut Formatter, level: usize, highlight: &Highlight) -> Result {
+    writeln!(
+        f,
+        "\n{} {}",
+        "*".repeat(level),
+        headline(&highlight.highlight)
+    )?;
+    writeln!(f, ":PROPERTIES:")?;
+    writeln!(f, ":ID:       {}", highlight.id)?;
+    writeln!(f, ":PAGE:     {}", highlight.page)?;
+    writeln!(
+        f,
+        ":CREATED:  [{}]",
+        highlight.timestamp.format("%Y-%m-%d %a %H:%M")
+    )?;
+    writeln!(f, ":END:")?;
+
+    writeln!(f, "#+begin_quote")?;
+    write_block_body(f, &highlight.highlight)?;
+    writeln!(f, "#+end_quote")?;
+
+    if let Some(note) = &highlight.note {
+        writeln!(f)?;
+        write_body(f, note)?;
+    }
+
+    Ok(())
+}
+
+/// Escapes the lines of a block the way Org does, with a comma before `*` and `#+` (after any
+/// indentation), so that they don't end the block. Org removes the comma again when reading it.
+fn write_block_body(f: &mut Formatter, text: &str) -> Result {
+    for line in text.lines() {
+        let content = line.trim_start_matches([' ', '\t']);
+        let unescaped = content.trim_start_matches(',');
+        if unescaped.starts_with('*') || unescaped.starts_with("#+") {
+            let indent = &line[..line.len() - content.len()];
+            writeln!(f, "{indent},{content}")?;
+        } else {
+            writeln!(f, "{line}")?;
+        }
+    }
+
+    Ok(())
+}
+
+// A line starting with `*` would otherwise become a headline and end the note
+fn write_body(f: &mut Formatter, text: &str) -> Result {
+    for line in text.lines() {
+        if line.starts_with('*') {
+            writeln!(f, " {line}")?;
+        } else {
+            writeln!(f, "{line}")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The first few words of a highlight, to tell highlights apart in the outline
+fn headline(text: &str) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+
+    if words.len() > HEADLINE_WORDS {
+        format!("{}…", words[..HEADLINE_WORDS].join(" "))
+    } else {
+        words.join(" ")
+    }
+}
+
+#[test]
+fn org_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    let org = Org(&boox).to_string();
+
+    assert!(org.starts_with(
+        ":PROPERTIES:
+:ID:       "
+    ));
+    assert!(org.contains(
+        ":END:
+#+TITLE: Building a Second Brain -- A Proven Method
+#+AUTHOR: Tiago Forte
+
+* PKM—or personal knowledge management
+:PROPERTIES:
+:ID:       30aa3585d76d8ebd
+:PAGE:     6
+:CREATED:  [2023-04-03 Mon 00:41]
+:END:
+#+begin_quote
+PKM—or personal knowledge management
+#+end_quote
+
+* Chapter 3: How a Second Brain Works
+
+** We bookmark articles to read later, but rarely…
+:PROPERTIES:
+:ID:       93d7c1a793812574
+:PAGE:     32
+:CREATED:  [2023-04-03 Mon 01:21]
+:END:
+#+begin_quote
+We bookmark articles to read later, but rarely find the time to revisit them again
+#+end_quote
+
+There's too many to \nactually read them all
+"
+    ));
+}
+
+#[test]
+fn block_escape_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, mut boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    let Section::HL(highlight) = &mut boox.sections[0] else {
+        panic!("expected a highlight");
+    };
+    highlight.highlight =
+        "* not a headline\n  #+end_quote\n,#+begin_src\nsee *this*\n#not a keyword".to_owned();
+
+    assert!(Org(&boox).to_string().contains(
+        "#+begin_quote
+,* not a headline
+  ,#+end_quote
+,,#+begin_src
+see *this*
+#not a keyword
+#+end_quote
+"
+    ));
+}
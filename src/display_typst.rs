@@ -0,0 +1,143 @@
+//! # `display_typst`
+//!
+//! Typst source for printed summaries: a heading per chapter, highlights as block quotes
+//! attributed to their page, and notes as footnotes. All text from the book is passed as
+//! string literals, so nothing in it is read as Typst markup.
+
+use std::fmt::{Display, Formatter, Result};
+
+use crate::{
+    model::{BooxFile, Highlight, Section},
+    script::Scripts,
+    text::normalise_whitespace,
+};
+
+pub struct Typst<'a>(pub &'a BooxFile);
+
+impl Display for Typst<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let boox = self.0;
+        let scripts = Scripts::of(boox);
+
+        let authors: Vec<String> = boox
+            .metadata
+            .authors
+            .iter()
+            .map(|a| typst_string(&a.to_string()))
+            .collect();
+        writeln!(
+            f,
+            "#set document(title: {}, author: ({}))",
+            typst_string(&boox.metadata.title),
+            authors.iter().map(|a| format!("{a},")).collect::<String>()
+        )?;
+        writeln!(f, "#set page(paper: \"a4\", numbering: \"1\")")?;
+
+        let locale = boox.provenance.as_ref().and_then(|p| p.locale.as_deref());
+        match locale {
+            Some(locale) => writeln!(f, "#set text(lang: {})", typst_string(locale))?,
+            None if scripts.cyrillic => writeln!(f, "#set text(lang: \"ru\")")?,
+            None => {}
+        }
+        if scripts.cjk {
+            writeln!(f, "// Contains CJK text, which needs a font that covers it")?;
+            writeln!(
+                f,
+                "#set text(font: (\"Libertinus Serif\", \"Noto Serif CJK SC\"))"
+            )?;
+        }
+        writeln!(f)?;
+
+        writeln!(
+            f,
+            "#align(center, text(size: 2em, weight: \"bold\", {}))",
+            typst_string(&boox.metadata.title)
+        )?;
+        if !authors.is_empty() {
+            writeln!(
+                f,
+                "#align(center, text(size: 1.2em, {}))",
+                typst_string(&boox.metadata.author_names())
+            )?;
+        }
+
+        for section in &boox.sections {
+            match section {
+                Section::Chapter(c) => {
+                    writeln!(f, "\n= #{}", typst_string(&normalise_whitespace(&c.title)))?;
+                }
+                Section::HL(highlight) => write_highlight(f, highlight)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_highlight(f: &mut Formatter, highlight: &Highlight) -> Result {
+    write!(
+        f,
+        "\n#quote(block: true, attribution: [p. {}], {})",
+        highlight.page,
+        typst_string(highlight.highlight.trim())
+    )?;
+    if let Some(note) = &highlight.note {
+        write!(f, "#footnote({})", typst_string(note.trim()))?;
+    }
+    writeln!(f)
+}
+
+/// A Typst string literal of `s`, line breaks included
+pub fn typst_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", u32::from(c))),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[test]
+fn typst_string_test() {
+    assert_eq!(
+        typst_string("#set \"quoted\" \\ *bold*\nnext"),
+        r##""#set \"quoted\" \\ *bold*\nnext""##
+    );
+}
+
+#[test]
+fn typst_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    let typst = Typst(&boox).to_string();
+
+    assert!(typst.starts_with(
+        "#set document(title: \"Building a Second Brain -- A Proven Method\", author: (\"Tiago Forte\",))
+#set page(paper: \"a4\", numbering: \"1\")
+
+#align(center, text(size: 2em, weight: \"bold\", \"Building a Second Brain -- A Proven Method\"))
+"
+    ));
+    assert!(typst.contains(
+        "= #\"Chapter 3: How a Second Brain Works\"
+
+#quote(block: true, attribution: [p. 32], \"We bookmark articles to read later, but rarely find the time to revisit them again\")\
+#footnote(\"There's too many to \\nactually read them all\")
+"
+    ));
+
+    let (_, boox) = parse_boox(include_str!("../test/data/russian_1.txt")).unwrap();
+    assert!(Typst(&boox)
+        .to_string()
+        .contains("#set text(lang: \"ru\")\n"));
+}
@@ -0,0 +1,294 @@
+//! # `epub`
+//!
+//! Small EPUB 3 book of the highlights, to read them again on the e-reader they came from.
+//! The first document holds the title and any highlights before the first chapter, followed by
+//! one XHTML document per chapter.
+
+use std::{
+    error::Error,
+    fmt::Write as _,
+    io::{Cursor, Write},
+};
+
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    highlight_id::book_id,
+    model::{BooxFile, Highlight, Section},
+    text::escape_html,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+const STYLE: &str = include_str!("../templates/epub.css");
+
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+/// A document of the book, with the chapter title if it has one
+struct Document<'a> {
+    file_name: String,
+    title: Option<&'a str>,
+    highlights: Vec<&'a Highlight>,
+}
+
+pub fn to_epub(boox: &BooxFile) -> Result<Vec<u8>> {
+    let mut documents = vec![Document {
+        file_name: "title.xhtml".to_owned(),
+        title: None,
+        highlights: vec![],
+    }];
+    for section in &boox.sections {
+        match section {
+            Section::Chapter(c) => documents.push(Document {
+                file_name: format!("chapter-{}.xhtml", documents.len()),
+                title: Some(&c.title),
+                highlights: vec![],
+            }),
+            Section::HL(highlight) => documents
+                .last_mut()
+                .expect("starts with the title page")
+                .highlights
+                .push(highlight),
+        }
+    }
+
+    let language = boox
+        .provenance
+        .as_ref()
+        .and_then(|p| p.locale.clone())
+        .unwrap_or_else(|| "und".to_owned());
+
+    // The mimetype has to come first and uncompressed so readers can sniff the format
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+
+    let options = SimpleFileOptions::default();
+    zip.start_file("META-INF/container.xml", options)?;
+    zip.write_all(CONTAINER.as_bytes())?;
+    zip.start_file("OEBPS/content.opf", options)?;
+    zip.write_all(package(boox, &documents, &language)?.as_bytes())?;
+    zip.start_file("OEBPS/nav.xhtml", options)?;
+    zip.write_all(nav(boox, &documents, &language)?.as_bytes())?;
+    zip.start_file("OEBPS/style.css", options)?;
+    zip.write_all(STYLE.as_bytes())?;
+    for document in &documents {
+        zip.start_file(format!("OEBPS/{}", document.file_name), options)?;
+        zip.write_all(xhtml(boox, document, &language)?.as_bytes())?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+fn package(boox: &BooxFile, documents: &[Document], language: &str) -> Result<String> {
+    // Derived from the highlights rather than the clock, so the same export gives the same book
+    let modified = boox
+        .sections
+        .iter()
+        .filter_map(|s| match s {
+            Section::HL(h) => Some(h.timestamp),
+            Section::Chapter(_) => None,
+        })
+        .max()
+        .unwrap_or_default();
+
+    let mut opf = String::new();
+    writeln!(opf, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        opf,
+        r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id">"#
+    )?;
+    writeln!(
+        opf,
+        r#"  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">"#
+    )?;
+    writeln!(
+        opf,
+        r#"    <dc:identifier id="book-id">urn:highlight-extract:{}</dc:identifier>"#,
+        book_id(&boox.metadata)
+    )?;
+    writeln!(
+        opf,
+        "    <dc:title>{}</dc:title>",
+        escape_html(&boox.metadata.title)
+    )?;
+    for author in &boox.metadata.authors {
+        writeln!(
+            opf,
+            "    <dc:creator>{}</dc:creator>",
+            escape_html(&author.to_string())
+        )?;
+    }
+    writeln!(
+        opf,
+        "    <dc:language>{}</dc:language>",
+        escape_html(language)
+    )?;
+    writeln!(
+        opf,
+        r#"    <meta property="dcterms:modified">{}</meta>"#,
+        modified.format("%Y-%m-%dT%H:%M:%SZ")
+    )?;
+    writeln!(opf, "  </metadata>")?;
+
+    writeln!(opf, "  <manifest>")?;
+    writeln!(
+        opf,
+        r#"    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>"#
+    )?;
+    writeln!(
+        opf,
+        r#"    <item id="style" href="style.css" media-type="text/css"/>"#
+    )?;
+    for (i, document) in documents.iter().enumerate() {
+        writeln!(
+            opf,
+            r#"    <item id="doc-{i}" href="{}" media-type="application/xhtml+xml"/>"#,
+            document.file_name
+        )?;
+    }
+    writeln!(opf, "  </manifest>")?;
+
+    writeln!(opf, "  <spine>")?;
+    for i in 0..documents.len() {
+        writeln!(opf, r#"    <itemref idref="doc-{i}"/>"#)?;
+    }
+    writeln!(opf, "  </spine>")?;
+    writeln!(opf, "</package>")?;
+
+    Ok(opf)
+}
+
+fn nav(boox: &BooxFile, documents: &[Document], language: &str) -> Result<String> {
+    let mut nav = xhtml_head("Contents", language)?;
+    writeln!(nav, r#"<nav epub:type="toc" id="toc">"#)?;
+    writeln!(nav, "<h1>Contents</h1>")?;
+    writeln!(nav, "<ol>")?;
+    for document in documents {
+        writeln!(
+            nav,
+            r#"<li><a href="{}">{}</a></li>"#,
+            document.file_name,
+            escape_html(document.title.unwrap_or(&boox.metadata.title))
+        )?;
+    }
+    writeln!(nav, "</ol>")?;
+    writeln!(nav, "</nav>")?;
+    writeln!(nav, "</body>\n</html>")?;
+
+    Ok(nav)
+}
+
+fn xhtml(boox: &BooxFile, document: &Document, language: &str) -> Result<String> {
+    let title = document.title.unwrap_or(&boox.metadata.title);
+    let mut out = xhtml_head(title, language)?;
+
+    match document.title {
+        Some(title) => writeln!(out, "<h2>{}</h2>", escape_html(title))?,
+        None => {
+            writeln!(out, "<h1>{}</h1>", escape_html(title))?;
+            if !boox.metadata.authors.is_empty() {
+                writeln!(
+                    out,
+                    r#"<p class="author">{}</p>"#,
+                    escape_html(&boox.metadata.author_names())
+                )?;
+            }
+        }
+    }
+
+    for highlight in &document.highlights {
+        writeln!(
+            out,
+            r#"<blockquote class="highlight" id="h-{}"><p>{}</p></blockquote>"#,
+            highlight.id,
+            text(&highlight.highlight)
+        )?;
+        writeln!(
+            out,
+            r#"<p class="meta">Page {} · {}</p>"#,
+            highlight.page,
+            highlight.timestamp.format("%Y-%m-%d %H:%M")
+        )?;
+        if let Some(note) = &highlight.note {
+            writeln!(out, r#"<aside class="note"><p>{}</p></aside>"#, text(note))?;
+        }
+    }
+
+    writeln!(out, "</body>\n</html>")?;
+
+    Ok(out)
+}
+
+fn xhtml_head(title: &str, language: &str) -> Result<String> {
+    let mut out = String::new();
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(
+        out,
+        r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" lang="{0}" xml:lang="{0}">"#,
+        escape_html(language)
+    )?;
+    writeln!(out, "<head>")?;
+    writeln!(out, "<title>{}</title>", escape_html(title))?;
+    writeln!(
+        out,
+        r#"<link rel="stylesheet" type="text/css" href="style.css"/>"#
+    )?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")?;
+
+    Ok(out)
+}
+
+/// Escaped text with its line breaks kept
+fn text(s: &str) -> String {
+    escape_html(s.trim_end()).replace('\n', "<br/>")
+}
+
+#[test]
+fn epub_test() {
+    use std::io::Read;
+
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    let epub = to_epub(&boox).unwrap();
+    assert_eq!(epub, to_epub(&boox).unwrap());
+
+    let mut zip = zip::ZipArchive::new(Cursor::new(epub)).unwrap();
+    let names: Vec<&str> = zip.file_names().collect();
+    assert_eq!(names.len(), 7);
+    assert_eq!(zip.by_index(0).unwrap().name(), "mimetype");
+    assert_eq!(
+        zip.by_index(0).unwrap().compression(),
+        CompressionMethod::Stored
+    );
+
+    let mut read = |name: &str| {
+        let mut s = String::new();
+        zip.by_name(name).unwrap().read_to_string(&mut s).unwrap();
+        s
+    };
+
+    let opf = read("OEBPS/content.opf");
+    assert!(opf.contains("<dc:title>Building a Second Brain -- A Proven Method</dc:title>"));
+    assert!(opf.contains("<dc:creator>Tiago Forte</dc:creator>"));
+    assert!(opf.contains(r#"<meta property="dcterms:modified">2023-04-03T17:01:00Z</meta>"#));
+
+    let nav = read("OEBPS/nav.xhtml");
+    assert!(nav
+        .contains(r#"<li><a href="chapter-1.xhtml">Chapter 3: How a Second Brain Works</a></li>"#));
+
+    let chapter = read("OEBPS/chapter-1.xhtml");
+    assert!(chapter.contains(
+        r#"<aside class="note"><p>There&#39;s too many to <br/>actually read them all</p></aside>"#
+    ));
+}
@@ -0,0 +1,107 @@
+//! # `highlight_id`
+//!
+//! Deterministic IDs for highlights, so re-exports of the same book can be matched up
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    model::{Metadata, Section},
+    text::normalise_whitespace,
+};
+
+/// Number of hex characters kept from the hash
+const ID_LENGTH: usize = 16;
+
+/// Derives an ID from the highlighted text, page and timestamp.
+/// Whitespace is normalised first, so re-wrapped text keeps its ID.
+pub fn highlight_id(text: &str, page: u32, timestamp: &NaiveDateTime) -> String {
+    let text = normalise_whitespace(text);
+    let key = format!(
+        "{text}\u{1f}{page}\u{1f}{}",
+        timestamp.format("%Y-%m-%dT%H:%M")
+    );
+
+    short_hash(&key)
+}
+
+/// Gives every repeat of an ID in the book an ID of its own, derived from the ID and the number
+/// of the occurrence, so the same passage highlighted twice within a minute stays two highlights.
+/// The first occurrence keeps its ID.
+pub fn disambiguate_ids(sections: &mut [Section]) {
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+
+    for section in sections {
+        let Section::HL(highlight) = section else {
+            continue;
+        };
+        let n = occurrences.entry(highlight.id.clone()).or_default();
+        *n += 1;
+        if *n > 1 {
+            highlight.id = short_hash(&format!("{}\u{1f}{n}", highlight.id));
+        }
+    }
+}
+
+/// Derives an ID for the book itself from its title and authors
+pub fn book_id(metadata: &Metadata) -> String {
+    short_hash(&format!(
+        "{}\u{1f}{}",
+        metadata.title,
+        metadata.author_names()
+    ))
+}
+
+/// The first `ID_LENGTH` hex characters of the SHA-256 of `key`
+fn short_hash(key: &str) -> String {
+    let mut hash = format!("{:x}", Sha256::digest(key.as_bytes()));
+    hash.truncate(ID_LENGTH);
+    hash
+}
+
+#[test]
+fn highlight_id_test() {
+    use chrono::NaiveDate;
+
+    let timestamp = NaiveDate::from_ymd_opt(2023, 4, 3)
+        .unwrap()
+        .and_hms_opt(0, 41, 0)
+        .unwrap();
+    let id = highlight_id("PKM—or personal knowledge management", 6, &timestamp);
+
+    assert_eq!(id, "30aa3585d76d8ebd");
+    assert_eq!(
+        highlight_id(" PKM—or  personal\nknowledge management\n", 6, &timestamp),
+        id
+    );
+    assert_ne!(
+        highlight_id("PKM—or personal knowledge management", 7, &timestamp),
+        id
+    );
+}
+
+#[test]
+fn duplicate_highlight_test() {
+    use crate::parse_boox::parse_boox;
+
+    let data = include_str!("../test/data/data.txt");
+    let last = data.rfind("2023-04-03 17:01").unwrap();
+    let data = format!("{data}{}", &data[last..]);
+
+    let (_, boox) = parse_boox(&data).unwrap();
+    let ids: Vec<&str> = boox
+        .sections
+        .iter()
+        .filter_map(|s| match s {
+            Section::HL(h) => Some(h.id.as_str()),
+            Section::Chapter(_) => None,
+        })
+        .collect();
+
+    assert_eq!(ids.len(), 5);
+    assert_eq!(ids[3], "450649f45bde8387");
+    assert_ne!(ids[4], ids[3]);
+    assert_eq!(parse_boox(&data).unwrap().1, boox);
+}
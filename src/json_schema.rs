@@ -0,0 +1,445 @@
+//! # `json_schema`
+//!
+//! The versioned json format written by `--json`.
+//! These types are deliberately separate from `model` so that refactoring the model does not
+//! change the output. Any change to them must bump `SCHEMA_VERSION` and add a migration.
+
+use std::path::PathBuf;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::model::{
+    Author, BooxFile, Chapter, Format, Highlight, Metadata, Provenance, Section, Span,
+};
+
+pub const SCHEMA_VERSION: u64 = 1;
+
+/// JSON Schema document describing the current version of the format
+pub const SCHEMA: &str = include_str!("../schema/highlight-extract.schema.json");
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonBook {
+    pub schema_version: u64,
+    pub metadata: JsonMetadata,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<JsonProvenance>,
+    pub sections: Vec<JsonSection>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonMetadata {
+    pub title: String,
+    pub authors: Vec<JsonAuthor>,
+    pub raw_title: String,
+    pub raw_author: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonAuthor {
+    pub first_name: String,
+    pub last_name: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonProvenance {
+    pub source_path: Option<PathBuf>,
+    pub file_hash: String,
+    pub format: JsonFormat,
+    pub locale: Option<String>,
+    #[serde(with = "iso_timestamp::option")]
+    pub exported_at: Option<NaiveDateTime>,
+    pub tool_version: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonFormat {
+    V1,
+    V2,
+    Json,
+    Other,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum JsonSection {
+    Chapter(JsonChapter),
+    Highlight(JsonHighlight),
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonChapter {
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<JsonSpan>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonHighlight {
+    #[serde(default)]
+    pub id: String,
+    #[serde(with = "iso_timestamp")]
+    pub timestamp: NaiveDateTime,
+    pub page: u32,
+    pub text: String,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<JsonSpan>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note_span: Option<JsonSpan>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonSpan {
+    pub start: usize,
+    pub end: usize,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// Timestamps are written as ISO 8601 without an offset, e.g. `2023-04-03T00:41:00`
+mod iso_timestamp {
+    use chrono::NaiveDateTime;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    const FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+    // Older output was written by chrono directly, which may include fractional seconds
+    const PARSE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+    pub fn serialize<S: Serializer>(t: &NaiveDateTime, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(&t.format(FORMAT))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<NaiveDateTime, D::Error> {
+        let s = String::deserialize(d)?;
+        NaiveDateTime::parse_from_str(&s, PARSE_FORMAT).map_err(serde::de::Error::custom)
+    }
+
+    pub mod option {
+        use super::{Deserialize, Deserializer, NaiveDateTime, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            t: &Option<NaiveDateTime>,
+            s: S,
+        ) -> Result<S::Ok, S::Error> {
+            match t {
+                Some(t) => super::serialize(t, s),
+                None => s.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            d: D,
+        ) -> Result<Option<NaiveDateTime>, D::Error> {
+            Option::<String>::deserialize(d)?
+                .map(|s| NaiveDateTime::parse_from_str(&s, super::PARSE_FORMAT))
+                .transpose()
+                .map_err(serde::de::Error::custom)
+        }
+    }
+}
+
+impl From<&BooxFile> for JsonBook {
+    fn from(boox: &BooxFile) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            metadata: (&boox.metadata).into(),
+            provenance: boox.provenance.as_ref().map(Into::into),
+            sections: boox.sections.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<&Metadata> for JsonMetadata {
+    fn from(metadata: &Metadata) -> Self {
+        Self {
+            title: metadata.title.clone(),
+            authors: metadata
+                .authors
+                .iter()
+                .map(|a| JsonAuthor {
+                    first_name: a.first_name.clone(),
+                    last_name: a.last_name.clone(),
+                })
+                .collect(),
+            raw_title: metadata.raw_title.clone(),
+            raw_author: metadata.raw_author.clone(),
+        }
+    }
+}
+
+impl From<&Provenance> for JsonProvenance {
+    fn from(provenance: &Provenance) -> Self {
+        Self {
+            source_path: provenance.source_path.clone(),
+            file_hash: provenance.file_hash.clone(),
+            format: match provenance.format {
+                Format::V1 => JsonFormat::V1,
+                Format::V2 => JsonFormat::V2,
+                Format::Json => JsonFormat::Json,
+                Format::Other => JsonFormat::Other,
+            },
+            locale: provenance.locale.clone(),
+            exported_at: provenance.exported_at,
+            tool_version: provenance.tool_version.clone(),
+        }
+    }
+}
+
+impl From<&Section> for JsonSection {
+    fn from(section: &Section) -> Self {
+        match section {
+            Section::Chapter(c) => Self::Chapter(JsonChapter {
+                title: c.title.clone(),
+                span: c.span.map(Into::into),
+            }),
+            Section::HL(h) => Self::Highlight(JsonHighlight {
+                id: h.id.clone(),
+                timestamp: h.timestamp,
+                page: h.page,
+                text: h.highlight.clone(),
+                note: h.note.clone(),
+                span: h.span.map(Into::into),
+                note_span: h.note_span.map(Into::into),
+            }),
+        }
+    }
+}
+
+impl From<Span> for JsonSpan {
+    fn from(span: Span) -> Self {
+        Self {
+            start: span.start,
+            end: span.end,
+            start_line: span.start_line,
+            end_line: span.end_line,
+        }
+    }
+}
+
+impl From<JsonBook> for BooxFile {
+    fn from(book: JsonBook) -> Self {
+        Self {
+            metadata: book.metadata.into(),
+            sections: book.sections.into_iter().map(Into::into).collect(),
+            provenance: book.provenance.map(Into::into),
+        }
+    }
+}
+
+impl From<JsonMetadata> for Metadata {
+    fn from(metadata: JsonMetadata) -> Self {
+        Self {
+            title: metadata.title,
+            authors: metadata
+                .authors
+                .into_iter()
+                .map(|a| Author {
+                    first_name: a.first_name,
+                    last_name: a.last_name,
+                })
+                .collect(),
+            raw_title: metadata.raw_title,
+            raw_author: metadata.raw_author,
+        }
+    }
+}
+
+impl From<JsonProvenance> for Provenance {
+    fn from(provenance: JsonProvenance) -> Self {
+        Self {
+            source_path: provenance.source_path,
+            file_hash: provenance.file_hash,
+            format: match provenance.format {
+                JsonFormat::V1 => Format::V1,
+                JsonFormat::V2 => Format::V2,
+                JsonFormat::Json => Format::Json,
+                JsonFormat::Other => Format::Other,
+            },
+            locale: provenance.locale,
+            exported_at: provenance.exported_at,
+            tool_version: provenance.tool_version,
+        }
+    }
+}
+
+impl From<JsonSection> for Section {
+    fn from(section: JsonSection) -> Self {
+        match section {
+            JsonSection::Chapter(c) => Self::Chapter(Chapter {
+                title: c.title,
+                span: c.span.map(Into::into),
+            }),
+            JsonSection::Highlight(h) => Self::HL(Highlight {
+                id: h.id,
+                timestamp: h.timestamp,
+                page: h.page,
+                highlight: h.text,
+                note: h.note,
+                span: h.span.map(Into::into),
+                note_span: h.note_span.map(Into::into),
+            }),
+        }
+    }
+}
+
+impl From<JsonSpan> for Span {
+    fn from(span: JsonSpan) -> Self {
+        Self {
+            start: span.start,
+            end: span.end,
+            start_line: span.start_line,
+            end_line: span.end_line,
+        }
+    }
+}
+
+/// Brings json written by any earlier version up to `SCHEMA_VERSION`
+pub fn migrate(mut value: Value) -> Result<Value, String> {
+    let version = match value.get("schema_version") {
+        None => 0,
+        Some(v) => v
+            .as_u64()
+            .ok_or_else(|| format!("invalid schema_version {v}"))?,
+    };
+
+    if version > SCHEMA_VERSION {
+        return Err(format!(
+            "schema_version {version} is newer than the supported version {SCHEMA_VERSION}"
+        ));
+    }
+
+    if version == 0 {
+        value = migrate_v0(value)?;
+    }
+
+    Ok(value)
+}
+
+// Version 0 is the unversioned output of earlier releases, which serialized the model directly
+fn migrate_v0(mut value: Value) -> Result<Value, String> {
+    let book = value.as_object_mut().ok_or("expected a json object")?;
+
+    if let Some(metadata) = book.get_mut("metadata") {
+        // The first releases only had the raw title and author strings
+        if metadata.get("raw_title").is_none() {
+            let title = metadata.get("title").and_then(Value::as_str).unwrap_or("");
+            let author = metadata.get("author").and_then(Value::as_str).unwrap_or("");
+            let migrated = JsonMetadata::from(&Metadata::from_raw(title, author));
+
+            *metadata = serde_json::to_value(migrated).map_err(|e| e.to_string())?;
+        }
+    }
+
+    if let Some(Value::Array(sections)) = book.get_mut("sections") {
+        for section in sections {
+            *section = migrate_v0_section(section.take())?;
+        }
+    }
+
+    book.insert("schema_version".to_owned(), json!(1));
+
+    Ok(value)
+}
+
+fn migrate_v0_section(section: Value) -> Result<Value, String> {
+    let Value::Object(section) = section else {
+        return Err("expected a section object".to_owned());
+    };
+
+    match section.into_iter().next() {
+        Some((tag, Value::Object(mut h))) if tag == "HL" => {
+            if let Some(text) = h.remove("highlight") {
+                h.insert("text".to_owned(), text);
+            }
+            h.insert("type".to_owned(), json!("highlight"));
+            Ok(Value::Object(h))
+        }
+        Some((tag, Value::String(title))) if tag == "Chapter" => {
+            Ok(json!({ "type": "chapter", "title": title }))
+        }
+        Some((tag, Value::Object(mut c))) if tag == "Chapter" => {
+            c.insert("type".to_owned(), json!("chapter"));
+            Ok(Value::Object(c))
+        }
+        _ => Err("unknown section".to_owned()),
+    }
+}
+
+#[test]
+fn schema_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    let value = serde_json::to_value(JsonBook::from(&boox)).unwrap();
+
+    assert_eq!(value["schema_version"], json!(1));
+    assert_eq!(
+        value["sections"][0],
+        json!({
+            "type": "highlight",
+            "id": "30aa3585d76d8ebd",
+            "timestamp": "2023-04-03T00:41:00",
+            "page": 6,
+            "text": "PKM—or personal knowledge management",
+            "note": null,
+            "span": { "start": 76, "end": 172, "start_line": 2, "end_line": 4 }
+        })
+    );
+    assert_eq!(
+        value["sections"][1],
+        json!({
+            "type": "chapter",
+            "title": "Chapter 3: How a Second Brain Works",
+            "span": { "start": 172, "end": 207, "start_line": 5, "end_line": 5 }
+        })
+    );
+
+    // The published schema must at least be valid json
+    let schema: Value = serde_json::from_str(SCHEMA).unwrap();
+    assert_eq!(
+        schema["properties"]["schema_version"]["const"],
+        json!(SCHEMA_VERSION)
+    );
+}
+
+#[test]
+fn migrate_test() {
+    let legacy = json!({
+        "metadata": {
+            "title": "One Up on Wall Street - Peter Lynch & John Rothchild (952)",
+            "author": "Peter Lynch; John Rothchild"
+        },
+        "sections": [
+            { "HL": {
+                "timestamp": "2022-03-07T01:11:00",
+                "page": 13,
+                "highlight": "tics to a degree",
+                "note": "some very good annotation"
+            } },
+            { "Chapter": "Chapter 1" }
+        ]
+    });
+
+    let book: JsonBook = serde_json::from_value(migrate(legacy).unwrap()).unwrap();
+
+    assert_eq!(book.schema_version, 1);
+    assert_eq!(book.metadata.title, "One Up on Wall Street");
+    assert_eq!(
+        book.sections[1],
+        JsonSection::Chapter(JsonChapter {
+            title: "Chapter 1".to_owned(),
+            span: None
+        })
+    );
+
+    let JsonSection::Highlight(h) = &book.sections[0] else {
+        panic!("expected a highlight");
+    };
+    assert_eq!(h.text, "tics to a degree");
+
+    assert!(migrate(json!({ "schema_version": SCHEMA_VERSION + 1 })).is_err());
+}
@@ -0,0 +1,470 @@
+//! # `library`
+//!
+//! A SQLite database of every book imported so far, with a full-text index over highlights
+//! and notes. Books are keyed by their metadata and highlights by their stable IDs within the
+//! book, so importing a newer export of a book updates it in place: changed notes are replaced,
+//! and highlights that are no longer in the export are removed. Exports older than the stored
+//! one are skipped, so that importing a folder of exports in any order ends with the newest.
+
+use std::{collections::HashSet, error::Error, path::Path};
+
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+
+use crate::{
+    highlight_id::book_id,
+    model::{BooxFile, Section},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Stored as `PRAGMA user_version`, to be bumped with every change to `SCHEMA`
+const SCHEMA_VERSION: u32 = 1;
+
+// Highlight IDs are only unique within a book. The rowid of a highlight is also its rowid in
+// `highlights_fts`, so that the index entry can be found without a scan.
+const SCHEMA: &str = "
+PRAGMA foreign_keys = ON;
+CREATE TABLE IF NOT EXISTS books (
+    id TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    authors TEXT NOT NULL,
+    raw_title TEXT NOT NULL,
+    raw_author TEXT NOT NULL,
+    source_path TEXT,
+    file_hash TEXT,
+    exported_at TEXT
+);
+CREATE TABLE IF NOT EXISTS chapters (
+    book_id TEXT NOT NULL REFERENCES books(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    PRIMARY KEY (book_id, position)
+);
+CREATE TABLE IF NOT EXISTS highlights (
+    rowid INTEGER PRIMARY KEY,
+    book_id TEXT NOT NULL REFERENCES books(id) ON DELETE CASCADE,
+    id TEXT NOT NULL,
+    chapter_position INTEGER,
+    position INTEGER NOT NULL,
+    page INTEGER NOT NULL,
+    timestamp TEXT NOT NULL,
+    text TEXT NOT NULL,
+    UNIQUE (book_id, id)
+);
+CREATE TABLE IF NOT EXISTS notes (
+    book_id TEXT NOT NULL,
+    highlight_id TEXT NOT NULL,
+    text TEXT NOT NULL,
+    PRIMARY KEY (book_id, highlight_id),
+    FOREIGN KEY (book_id, highlight_id) REFERENCES highlights(book_id, id) ON DELETE CASCADE
+);
+CREATE VIRTUAL TABLE IF NOT EXISTS highlights_fts USING fts5(
+    text, note, tokenize = 'unicode61 remove_diacritics 2'
+);
+";
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+pub struct Library {
+    db: Connection,
+}
+
+/// What an import changed in the library
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ImportStats {
+    pub added: usize,
+    /// Highlights whose text or note changed
+    pub updated: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SearchHit {
+    pub title: String,
+    pub authors: String,
+    pub chapter: Option<String>,
+    pub page: u32,
+    pub timestamp: String,
+    /// The matching text with the matches marked as `**bold**`
+    pub snippet: String,
+    pub highlight_id: String,
+}
+
+impl Library {
+    /// Opens the library at `path`, creating it if it doesn't exist yet
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Opens the library at `path`, which must exist already
+    pub fn open_existing(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Err("no such library, import books into it first".into());
+        }
+        let flags = OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        Self::from_connection(Connection::open_with_flags(path, flags)?)
+    }
+
+    fn from_connection(db: Connection) -> Result<Self> {
+        let version: u32 = db.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(format!(
+                "library version {version} is newer than the supported version {SCHEMA_VERSION}"
+            )
+            .into());
+        }
+        db.execute_batch(SCHEMA)?;
+        db.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(Self { db })
+    }
+
+    /// Inserts the book or brings the stored copy up to date with `boox`. Returns `None` without
+    /// changing anything when the library already has a newer export of the book.
+    pub fn import(&mut self, boox: &BooxFile) -> Result<Option<ImportStats>> {
+        let book_id = book_id(&boox.metadata);
+        let provenance = boox.provenance.as_ref();
+        let exported_at = provenance
+            .and_then(|p| p.exported_at)
+            .map(|t| t.format(TIMESTAMP_FORMAT).to_string());
+        let tx = self.db.transaction()?;
+        let mut stats = ImportStats::default();
+
+        // The timestamps are zero-padded, so they compare like the times they stand for
+        let stored_exported_at: Option<String> = tx
+            .query_row(
+                "SELECT exported_at FROM books WHERE id = ?1",
+                [&book_id],
+                |r| r.get(0),
+            )
+            .optional()?
+            .flatten();
+        let newer = match (&stored_exported_at, &exported_at) {
+            (Some(stored), Some(new)) if stored > new => return Ok(None),
+            (Some(_), Some(_)) => true,
+            _ => false,
+        };
+
+        tx.execute(
+            "INSERT INTO books (id, title, authors, raw_title, raw_author, source_path, file_hash, exported_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            ON CONFLICT (id) DO UPDATE SET
+                title = excluded.title, authors = excluded.authors,
+                raw_title = excluded.raw_title, raw_author = excluded.raw_author,
+                source_path = excluded.source_path, file_hash = excluded.file_hash,
+                exported_at = coalesce(excluded.exported_at, exported_at)",
+            params![
+                book_id,
+                boox.metadata.title,
+                boox.metadata.author_names(),
+                boox.metadata.raw_title,
+                boox.metadata.raw_author,
+                provenance
+                    .and_then(|p| p.source_path.as_ref())
+                    .map(|p| p.to_string_lossy()),
+                provenance.map(|p| &p.file_hash),
+                exported_at,
+            ],
+        )?;
+        tx.execute("DELETE FROM chapters WHERE book_id = ?1", [&book_id])?;
+
+        let mut chapter = None;
+        let mut seen = HashSet::new();
+        for (position, section) in boox.sections.iter().enumerate() {
+            let highlight = match section {
+                Section::Chapter(c) => {
+                    tx.execute(
+                        "INSERT INTO chapters (book_id, position, title) VALUES (?1, ?2, ?3)",
+                        params![book_id, position, c.title],
+                    )?;
+                    chapter = Some(position);
+                    continue;
+                }
+                Section::HL(highlight) => highlight,
+            };
+
+            let stored: Option<(String, Option<String>)> = tx
+                .query_row(
+                    "SELECT h.text, n.text FROM highlights h
+                    LEFT JOIN notes n ON n.book_id = h.book_id AND n.highlight_id = h.id
+                    WHERE h.book_id = ?1 AND h.id = ?2",
+                    [&book_id, &highlight.id],
+                    |r| Ok((r.get(0)?, r.get(1)?)),
+                )
+                .optional()?;
+            match stored {
+                None => stats.added += 1,
+                Some((text, note)) if text == highlight.highlight && note == highlight.note => {
+                    stats.unchanged += 1;
+                }
+                Some(_) => stats.updated += 1,
+            }
+
+            let rowid: i64 = tx.query_row(
+                "INSERT INTO highlights (book_id, id, chapter_position, position, page, timestamp, text)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                ON CONFLICT (book_id, id) DO UPDATE SET
+                    chapter_position = excluded.chapter_position,
+                    position = excluded.position, page = excluded.page,
+                    timestamp = excluded.timestamp, text = excluded.text
+                RETURNING rowid",
+                params![
+                    book_id,
+                    highlight.id,
+                    chapter,
+                    position,
+                    highlight.page,
+                    highlight.timestamp.format(TIMESTAMP_FORMAT).to_string(),
+                    highlight.highlight,
+                ],
+                |r| r.get(0),
+            )?;
+            match &highlight.note {
+                Some(note) => tx.execute(
+                    "INSERT INTO notes (book_id, highlight_id, text) VALUES (?1, ?2, ?3)
+                    ON CONFLICT (book_id, highlight_id) DO UPDATE SET text = excluded.text",
+                    params![book_id, highlight.id, note],
+                )?,
+                None => tx.execute(
+                    "DELETE FROM notes WHERE book_id = ?1 AND highlight_id = ?2",
+                    [&book_id, &highlight.id],
+                )?,
+            };
+
+            tx.execute("DELETE FROM highlights_fts WHERE rowid = ?1", [rowid])?;
+            tx.execute(
+                "INSERT INTO highlights_fts (rowid, text, note) VALUES (?1, ?2, ?3)",
+                params![rowid, highlight.highlight, highlight.note],
+            )?;
+            seen.insert(highlight.id.as_str());
+        }
+
+        // Without export times to compare, highlights taken after the last one in the export may
+        // just be missing from an older export, so they are kept
+        let last_timestamp = boox
+            .sections
+            .iter()
+            .filter_map(|s| match s {
+                Section::HL(h) => Some(h.timestamp.format(TIMESTAMP_FORMAT).to_string()),
+                Section::Chapter(_) => None,
+            })
+            .max();
+        let stored: Vec<(i64, String, String)> = tx
+            .prepare("SELECT rowid, id, timestamp FROM highlights WHERE book_id = ?1")?
+            .query_map([&book_id], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        let removed = stored.iter().filter(|(_, id, timestamp)| {
+            !seen.contains(id.as_str()) && (newer || Some(timestamp) <= last_timestamp.as_ref())
+        });
+        for (rowid, _, _) in removed {
+            tx.execute("DELETE FROM highlights_fts WHERE rowid = ?1", [rowid])?;
+            tx.execute("DELETE FROM highlights WHERE rowid = ?1", [rowid])?;
+            stats.removed += 1;
+        }
+
+        tx.commit()?;
+        Ok(Some(stats))
+    }
+
+    /// Runs an FTS5 query like `habit OR "second brain"` over highlights and notes, best matches
+    /// first. Use `quote_query` for queries typed by users that aren't meant as FTS5 syntax.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        let hits = self
+            .db
+            .prepare(
+                "SELECT b.title, b.authors, c.title, h.page, h.timestamp,
+                    snippet(highlights_fts, -1, '**', '**', '…', 24), h.id
+                FROM highlights_fts
+                JOIN highlights h ON h.rowid = highlights_fts.rowid
+                JOIN books b ON b.id = h.book_id
+                LEFT JOIN chapters c ON c.book_id = h.book_id AND c.position = h.chapter_position
+                WHERE highlights_fts MATCH ?1
+                ORDER BY rank
+                LIMIT ?2",
+            )?
+            .query_map(params![query, limit], |r| {
+                Ok(SearchHit {
+                    title: r.get(0)?,
+                    authors: r.get(1)?,
+                    chapter: r.get(2)?,
+                    page: r.get(3)?,
+                    timestamp: r.get(4)?,
+                    snippet: r.get(5)?,
+                    highlight_id: r.get(6)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(hits)
+    }
+}
+
+/// Quotes every word of `query` as an FTS5 string, so that `don't` or `second-brain` are looked
+/// up as they are instead of being parsed as operators. All words have to match.
+pub fn quote_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|w| format!("\"{}\"", w.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[test]
+fn quote_query_test() {
+    assert_eq!(quote_query("don't"), "\"don't\"");
+    assert_eq!(
+        quote_query(" second-brain  \"habit\" "),
+        "\"second-brain\" \"\"\"habit\"\"\""
+    );
+}
+
+#[test]
+fn library_test() {
+    use crate::{model::Provenance, parse_boox::parse_boox};
+
+    let mut library = Library::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+    let data = include_str!("../test/data/data.txt");
+    let (_, mut boox) = parse_boox(data).unwrap();
+    let export = Path::new("Second Brain-annotation-2023-04-04_10_00_00.txt");
+    boox.provenance = Some(Provenance::new(Some(export), data));
+
+    assert_eq!(
+        library.import(&boox).unwrap(),
+        Some(ImportStats {
+            added: 4,
+            ..ImportStats::default()
+        })
+    );
+
+    let hits = library.search("bookmark", 10).unwrap();
+    assert_eq!(
+        hits,
+        vec![SearchHit {
+            title: "Building a Second Brain -- A Proven Method".to_owned(),
+            authors: "Tiago Forte".to_owned(),
+            chapter: Some("Chapter 3: How a Second Brain Works".to_owned()),
+            page: 32,
+            timestamp: "2023-04-03T01:21:00".to_owned(),
+            snippet: "We **bookmark** articles to read later, but rarely find the time to revisit them again"
+                .to_owned(),
+            highlight_id: "93d7c1a793812574".to_owned(),
+        }]
+    );
+    assert_eq!(library.search("LLMs", 10).unwrap().len(), 1);
+    assert!(library.search("second-brain", 10).is_err());
+    assert_eq!(
+        library
+            .search(&quote_query("read-later there's"), 10)
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // A newer export with a changed note and a highlight removed on the device
+    let Section::HL(highlight) = &mut boox.sections[2] else {
+        panic!("expected a highlight");
+    };
+    highlight.note = Some("Saved for the weekend".to_owned());
+    boox.sections.pop();
+    let export = Path::new("Second Brain-annotation-2023-04-05_10_00_00.txt");
+    boox.provenance = Some(Provenance::new(Some(export), data));
+
+    assert_eq!(
+        library.import(&boox).unwrap(),
+        Some(ImportStats {
+            updated: 1,
+            unchanged: 2,
+            removed: 1,
+            ..ImportStats::default()
+        })
+    );
+    assert!(library.search("actually", 10).unwrap().is_empty());
+    assert_eq!(library.search("weekend", 10).unwrap().len(), 1);
+    assert!(library.search("Multimedia", 10).unwrap().is_empty());
+
+    let highlights: i64 = library
+        .db
+        .query_row("SELECT count(*) FROM highlights", [], |r| r.get(0))
+        .unwrap();
+    assert_eq!(highlights, 3);
+
+    assert_eq!(
+        library.import(&boox).unwrap(),
+        Some(ImportStats {
+            unchanged: 3,
+            ..ImportStats::default()
+        })
+    );
+}
+
+#[test]
+fn older_export_test() {
+    use crate::{
+        model::{Highlight, Provenance},
+        parse_boox::parse_boox,
+    };
+
+    let mut library = Library::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+    let data = include_str!("../test/data/data.txt");
+    let (_, mut boox) = parse_boox(data).unwrap();
+
+    let newer = Path::new("Second Brain-annotation-2023-04-05_10_00_00.txt");
+    boox.provenance = Some(Provenance::new(Some(newer), data));
+    assert!(library.import(&boox).unwrap().is_some());
+
+    // An export from before the last highlight was taken must not remove it
+    let older = Path::new("Second Brain-annotation-2023-04-03_01_00_00.txt");
+    boox.provenance = Some(Provenance::new(Some(older), data));
+    boox.sections.pop();
+    assert_eq!(library.import(&boox).unwrap(), None);
+    assert_eq!(library.search("Multimedia", 10).unwrap().len(), 1);
+
+    // Without an export time, only highlights from before the last one in the file are removed
+    boox.provenance = Some(Provenance::new(None, data));
+    assert_eq!(library.import(&boox).unwrap().unwrap().removed, 0);
+
+    let Some(Section::HL(last)) = boox.sections.pop() else {
+        panic!("expected a highlight");
+    };
+    boox.sections.push(Section::HL(Highlight {
+        timestamp: last.timestamp + chrono::Duration::days(1),
+        ..last
+    }));
+    assert_eq!(library.import(&boox).unwrap().unwrap().removed, 1);
+}
+
+#[test]
+fn open_existing_test() {
+    assert!(Library::open_existing(Path::new("test/data/missing.db")).is_err());
+}
+
+#[test]
+fn shared_id_test() {
+    use crate::parse_boox::parse_boox;
+
+    let mut library = Library::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+    let (_, mut boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    library.import(&boox).unwrap();
+
+    // A second edition with the same highlights gets its own copies instead of taking them over
+    boox.metadata.title = "Building a Second Brain (2nd Edition)".to_owned();
+    assert_eq!(library.import(&boox).unwrap().unwrap().added, 4);
+
+    let hits = library.search("bookmark", 10).unwrap();
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].highlight_id, hits[1].highlight_id);
+    assert_ne!(hits[0].title, hits[1].title);
+}
+
+#[test]
+fn schema_version_test() {
+    let db = Connection::open_in_memory().unwrap();
+    db.pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+        .unwrap();
+
+    let error = Library::from_connection(db).err().unwrap();
+    assert_eq!(
+        error.to_string(),
+        "library version 2 is newer than the supported version 1"
+    );
+}
@@ -1,43 +1,621 @@
-use std::{fs, path::PathBuf};
-
-use crate::display_markdown::DisplayMarkdown;
-use clap::Parser;
+use std::{
+    error::Error,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process, slice,
+};
+
+use crate::{
+    anki::{to_apkg, AnkiOptions, Cloze, DeckLayout},
+    batch::{expand_input, file_name, DEFAULT_FILE_NAME},
+    binary_cache::{from_binary, is_binary_cache, to_binary, BinaryFormat},
+    citation::{citation_key, to_bibtex, to_csl_json, Publication},
+    display_html::Html,
+    display_latex::Latex,
+    display_logseq::Logseq,
+    display_markdown::{BooxMarkdown, DisplayMarkdown, MarkdownOptions},
+    display_obsidian::Obsidian,
+    display_org::Org,
+    display_typst::Typst,
+    epub::to_epub,
+    json_schema::SCHEMA,
+    library::{quote_query, Library},
+    model::{BooxFile, Provenance},
+    outline::{to_roam_json, Opml},
+    pandoc::to_pandoc_json,
+    provenance::FrontMatter,
+    serialize::CsvProfile,
+    slug::unique_path,
+    template::{render_template, DEFAULT_TEMPLATE},
+    web_annotation::to_web_annotation,
+    xlsx::{to_xlsx, SheetLayout},
+};
+use chrono::{FixedOffset, Utc};
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand, ValueEnum};
 use parse_boox::parse_boox;
-
+use parse_boox_json::{is_json, parse_boox_json};
+
+mod anki;
+mod batch;
+mod binary_cache;
+mod citation;
+mod display_html;
+mod display_latex;
+mod display_logseq;
 mod display_markdown;
+mod display_obsidian;
+mod display_org;
+mod display_typst;
+mod epub;
+mod highlight_id;
+mod json_schema;
+mod library;
 mod model;
 mod nom_util;
+mod outline;
+mod pandoc;
 mod parse_boox;
+mod parse_boox_json;
 mod parse_boox_v1;
 mod parse_boox_v2;
+mod parse_metadata;
+mod provenance;
+mod script;
+mod serialize;
+mod slug;
+mod span;
+mod template;
+mod text;
+mod web_annotation;
+mod xlsx;
+
+const DEFAULT_LIBRARY: &str = "highlights.db";
+/// The name of the single file that formats holding several books are written to
+const MULTI_BOOK_FILE_STEM: &str = "highlights";
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    /// The highlight file to parse
-    #[arg()]
-    input_file: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// The highlight files to parse, or json or caches previously written by this tool.
+    /// Directories are searched for exports, and patterns like `exports/*.txt` are expanded.
+    #[arg(required_unless_present_any = ["print_schema", "print_template"])]
+    input_files: Vec<PathBuf>,
+
+    /// Write a file per book into this directory instead of printing to stdout
+    #[arg(short, long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+
+    /// The name of the files in the output directory, from `{title}`, `{author}`,
+    /// `{stem}` (the input file name) and `{ext}`
+    #[arg(long, value_name = "TEMPLATE", default_value = DEFAULT_FILE_NAME)]
+    file_name: String,
 
-    /// Turn debugging information on
-    #[arg(short, long, default_value_t = false)]
+    /// Transliterate titles and authors in file names to ASCII, e.g. `Chistaia arkhitektura`
+    #[arg(long, default_value_t = false)]
+    ascii_file_names: bool,
+
+    /// The format to print the highlights in
+    #[arg(long, value_enum, default_value_t = OutputFormat::Markdown)]
+    output_format: OutputFormat,
+
+    /// Shorthand for `--output-format json`
+    #[arg(short, long, default_value_t = false, conflicts_with = "output_format")]
     json: bool,
+
+    /// Include the location of every highlight, note and chapter in the input in the json output
+    #[arg(long, default_value_t = false)]
+    spans: bool,
+
+    /// The columns of the csv and tsv output
+    #[arg(long, value_enum, default_value_t = CsvProfile::Full)]
+    csv_profile: CsvProfile,
+
+    /// Hide every occurrence of this phrase on the front of the Anki cards
+    #[arg(long, value_name = "PHRASE")]
+    cloze: Option<String>,
+
+    /// Hide the text of each highlight's note on the front of the Anki cards
+    #[arg(long, default_value_t = false, conflicts_with = "cloze")]
+    cloze_note: bool,
+
+    /// Whether the Anki output gets a deck per book or one deck for the library
+    #[arg(long, value_enum, default_value_t = DeckLayout::Book)]
+    anki_decks: DeckLayout,
+
+    /// Whether the xlsx output gets a sheet per book or one sheet for all books
+    #[arg(long, value_enum, default_value_t = SheetLayout::Book)]
+    sheets: SheetLayout,
+
+    /// Add a `^id` block anchor with the stable ID of each highlight to the Markdown output
+    #[arg(long, default_value_t = false)]
+    block_ids: bool,
+
+    /// End each highlight in the Markdown output with a Pandoc citation of its page, like `[@lynch1989, p. 13]`
+    #[arg(long, default_value_t = false)]
+    cite: bool,
+
+    /// The ISBN for the bibtex and csl-json entries and the web-annotation source
+    #[arg(long)]
+    isbn: Option<String>,
+
+    /// The UTC offset the device clock was set to, like `+02:00`, for the web-annotation times,
+    /// which are written without an offset otherwise
+    #[arg(long, value_name = "OFFSET", allow_hyphen_values = true)]
+    timezone: Option<FixedOffset>,
+
+    /// The publisher for the bibtex and csl-json entries
+    #[arg(long)]
+    publisher: Option<String>,
+
+    /// The year of publication for the bibliography entries and citation keys
+    #[arg(long)]
+    year: Option<i32>,
+
+    /// Prefix the Markdown output with YAML front matter describing the source export
+    #[arg(long, default_value_t = false)]
+    front_matter: bool,
+
+    /// Render the Markdown output with a Jinja-style template instead of the built-in layout
+    #[arg(long, value_name = "PATH")]
+    template: Option<PathBuf>,
+
+    /// Print the built-in Markdown layout as a template to start customising from and exit
+    #[arg(long, default_value_t = false)]
+    print_template: bool,
+
+    /// Print the JSON Schema of the json output and exit
+    #[arg(long, default_value_t = false)]
+    print_schema: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Add books to a library database, or update them from newer exports
+    Import {
+        /// The library database, created if it doesn't exist yet
+        #[arg(long, default_value = DEFAULT_LIBRARY)]
+        library: PathBuf,
+
+        /// The highlight files, json or caches to import, or directories of exports
+        #[arg(required = true)]
+        input_files: Vec<PathBuf>,
+    },
+    /// Search the highlights and notes in a library database
+    Search {
+        /// The library database
+        #[arg(long, default_value = DEFAULT_LIBRARY)]
+        library: PathBuf,
+
+        /// Words to look for, all of which have to match
+        #[arg(required = true, num_args = 1..)]
+        query: Vec<String>,
+
+        /// Read the query as FTS5 syntax, like `habit OR "second brain"`
+        #[arg(long, default_value_t = false)]
+        fts: bool,
+
+        /// The maximum number of results
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
 }
 
-// Take a path to a Boox file and print it as Markdown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Markdown,
+    /// Markdown with front matter, block anchors and callouts for Obsidian vaults
+    Obsidian,
+    /// Outline of nested blocks with properties for Logseq graphs
+    Logseq,
+    /// Outline of chapters, highlights and notes for outliners
+    Opml,
+    /// Roam Research import file with a page for the book
+    Roam,
+    /// Org-mode document with org-roam IDs for Emacs
+    Org,
+    /// Self-contained web page with a table of contents, for sharing
+    Html,
+    /// LaTeX source for a printed summary, notes as footnotes
+    Latex,
+    /// Typst source for a printed summary, notes as footnotes
+    Typst,
+    /// BibTeX entry for citing the book from LaTeX, see `--cite`
+    Bibtex,
+    /// CSL-JSON entry for citing the book with Pandoc or Zotero, see `--cite`
+    CslJson,
+    /// W3C Web Annotation collection in JSON-LD
+    WebAnnotation,
+    /// Pandoc JSON AST, to convert further with `pandoc -f json`
+    Pandoc,
+    Json,
+    JsonPretty,
+    /// One highlight per line with the book metadata repeated
+    #[value(alias = "jsonl")]
+    JsonLines,
+    Yaml,
+    Toml,
+    /// One highlight per row, see `--csv-profile`
+    Csv,
+    /// Like csv, separated by tabs
+    Tsv,
+    /// Excel workbook with a row per highlight, see `--sheets`
+    Xlsx,
+    /// E-book of the highlights with a document per chapter
+    Epub,
+    /// Anki deck package with a note per highlight
+    #[value(alias = "apkg")]
+    Anki,
+    /// Binary cache encoded as CBOR, which can be read back much faster than the export
+    Cbor,
+    /// Binary cache encoded as MessagePack, for tools that read msgpack rather than CBOR
+    #[value(alias = "msgpack")]
+    MessagePack,
+}
+
+// Take paths to Boox files and print them as Markdown, or write a file per book
 fn main() {
     let cli = Cli::parse();
-    let data = fs::read_to_string(cli.input_file).unwrap();
 
-    let boox = parse_boox(&data).unwrap().1;
+    if let Some(command) = cli.command {
+        run_command(command);
+        return;
+    }
+    if cli.print_schema {
+        print!("{SCHEMA}");
+        return;
+    }
+    if cli.print_template {
+        print!("{DEFAULT_TEMPLATE}");
+        return;
+    }
 
-    if cli.json {
-        let s = serde_json::to_string(&boox).unwrap();
-        print!("{s}");
+    let format = if cli.json {
+        OutputFormat::Json
     } else {
-        println!("{}", DisplayMarkdown(&boox));
+        cli.output_format
+    };
+    if cli.template.is_some() && format != OutputFormat::Markdown {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--template only applies to the markdown output format",
+            )
+            .exit();
+    }
+    let template = cli
+        .template
+        .as_ref()
+        .map(|t| fs::read_to_string(t).unwrap_or_else(|e| fail(t, e.into())));
+
+    let mut input_files = vec![];
+    let mut failures = 0;
+    for input in &cli.input_files {
+        match expand_input(input) {
+            Ok(files) => input_files.extend(files),
+            Err(e) => {
+                eprintln!("error: {}: {e}", input.display());
+                failures += 1;
+            }
+        }
+    }
+    if format.is_binary() && cli.output_dir.is_none() && input_files.len() > 1 {
+        let format = format.to_possible_value().expect("no skipped formats");
+        Cli::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                format!(
+                    "--output-format {} writes a file per book, pass --output-dir for several books",
+                    format.get_name()
+                ),
+            )
+            .exit();
+    }
+    let batch = cli.output_dir.is_some() || cli.input_files.len() > 1 || input_files.len() > 1;
+
+    let mut books = vec![];
+    let mut written = 0;
+    let mut outputs = vec![];
+    for input_file in &input_files {
+        let result = read_boox(input_file).and_then(|mut boox| {
+            if !cli.spans {
+                boox.clear_spans();
+            }
+            if format.is_multi_book() {
+                books.push(boox);
+                return Ok(None);
+            }
+            let data = render(&cli, format, template.as_deref(), &boox)?;
+            let name = file_name(
+                &cli.file_name,
+                &boox,
+                input_file,
+                format.extension(),
+                cli.ascii_file_names,
+            );
+            write_output(cli.output_dir.as_deref(), &name, &data, &mut outputs)
+        });
+
+        match result {
+            Ok(output) => {
+                written += 1;
+                if let Some(output) = output {
+                    eprintln!("{} -> {}", input_file.display(), output.display());
+                }
+            }
+            Err(e) => {
+                eprintln!("error: {}: {e}", input_file.display());
+                failures += 1;
+            }
+        }
+    }
+
+    // Formats holding a whole library get every book in one file
+    if format.is_multi_book() && !books.is_empty() {
+        let name = PathBuf::from(format!("{MULTI_BOOK_FILE_STEM}.{}", format.extension()));
+        let output = render_books(&cli, format, &books)
+            .and_then(|data| write_output(cli.output_dir.as_deref(), &name, &data, &mut outputs));
+        match output {
+            Ok(Some(output)) => eprintln!("{} books -> {}", books.len(), output.display()),
+            Ok(None) => {}
+            Err(e) => {
+                // None of the books made it into the file
+                eprintln!("error: {}: {e}", name.display());
+                written -= books.len();
+                failures += books.len();
+            }
+        }
+    }
+
+    if batch {
+        eprintln!("{written} succeeded, {failures} failed");
+    }
+    if failures > 0 {
+        process::exit(1);
     }
 }
 
+impl OutputFormat {
+    /// Whether the format can hold several books, which then go into a single file
+    const fn is_multi_book(self) -> bool {
+        matches!(self, Self::Xlsx | Self::Anki)
+    }
+
+    /// Whether the format is a binary file for a single book, which can't be concatenated on
+    /// stdout like text
+    const fn is_binary(self) -> bool {
+        matches!(self, Self::Epub | Self::Cbor | Self::MessagePack)
+    }
+
+    const fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown | Self::Obsidian | Self::Logseq => "md",
+            Self::Opml => "opml",
+            Self::Org => "org",
+            Self::Html => "html",
+            Self::Latex => "tex",
+            Self::Typst => "typ",
+            Self::Bibtex => "bib",
+            Self::WebAnnotation => "jsonld",
+            Self::Roam | Self::CslJson | Self::Pandoc | Self::Json | Self::JsonPretty => "json",
+            Self::JsonLines => "jsonl",
+            Self::Yaml => "yaml",
+            Self::Toml => "toml",
+            Self::Csv => "csv",
+            Self::Tsv => "tsv",
+            Self::Xlsx => "xlsx",
+            Self::Epub => "epub",
+            Self::Anki => "apkg",
+            Self::Cbor => "cbor",
+            Self::MessagePack => "msgpack",
+        }
+    }
+}
+
+/// Renders one book in `format`, with `template` replacing the built-in Markdown layout
+fn render(
+    cli: &Cli,
+    format: OutputFormat,
+    template: Option<&str>,
+    boox: &BooxFile,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let publication = Publication {
+        isbn: cli.isbn.clone(),
+        publisher: cli.publisher.clone(),
+        year: cli.year,
+    };
+    let key = citation_key(&boox.metadata, &publication);
+
+    let text = match format {
+        OutputFormat::Markdown => {
+            let mut text = String::new();
+            if cli.front_matter {
+                text += &DisplayMarkdown(&FrontMatter(boox)).to_string();
+            }
+            let options = MarkdownOptions {
+                block_ids: cli.block_ids,
+                citation_key: cli.cite.then_some(key.as_str()),
+            };
+            match template {
+                Some(template) => text += &render_template(boox, template, options)?,
+                None => text += &DisplayMarkdown(&BooxMarkdown(boox, options)).to_string(),
+            }
+            text + "\n"
+        }
+        OutputFormat::Obsidian => format!("{}\n", DisplayMarkdown(&Obsidian(boox))),
+        OutputFormat::Logseq => DisplayMarkdown(&Logseq(boox)).to_string(),
+        OutputFormat::Opml => Opml(boox).to_string(),
+        OutputFormat::Roam => format!("{}\n", to_roam_json(boox)),
+        OutputFormat::Org => Org(boox).to_string(),
+        OutputFormat::Html => Html(boox).to_string(),
+        OutputFormat::Latex => Latex(boox).to_string(),
+        OutputFormat::Typst => Typst(boox).to_string(),
+        OutputFormat::Bibtex => to_bibtex(&boox.metadata, &publication),
+        OutputFormat::CslJson => format!("{}\n", to_csl_json(&boox.metadata, &publication)),
+        OutputFormat::WebAnnotation => format!(
+            "{:#}\n",
+            to_web_annotation(boox, publication.isbn.as_deref(), cli.timezone)
+        ),
+        OutputFormat::Pandoc => to_pandoc_json(boox)? + "\n",
+        OutputFormat::Json => serialize::to_json(boox, false)?,
+        OutputFormat::JsonPretty => serialize::to_json(boox, true)? + "\n",
+        OutputFormat::JsonLines => serialize::to_json_lines(boox)?,
+        OutputFormat::Yaml => serialize::to_yaml(boox)?,
+        OutputFormat::Toml => serialize::to_toml(boox)?,
+        OutputFormat::Csv => serialize::to_csv(boox, b',', cli.csv_profile)?,
+        OutputFormat::Tsv => serialize::to_csv(boox, b'\t', cli.csv_profile)?,
+        OutputFormat::Xlsx | OutputFormat::Anki => {
+            return render_books(cli, format, slice::from_ref(boox))
+        }
+        OutputFormat::Epub => return to_epub(boox),
+        OutputFormat::Cbor => return to_binary(boox, BinaryFormat::Cbor),
+        OutputFormat::MessagePack => return to_binary(boox, BinaryFormat::MessagePack),
+    };
+
+    Ok(text.into_bytes())
+}
+
+/// Renders several books into one file of a format for which `is_multi_book` holds
+fn render_books(
+    cli: &Cli,
+    format: OutputFormat,
+    books: &[BooxFile],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    match format {
+        OutputFormat::Xlsx => to_xlsx(books, cli.sheets),
+        OutputFormat::Anki => {
+            let cloze = match (&cli.cloze, cli.cloze_note) {
+                (Some(phrase), _) => Some(Cloze::Phrase(phrase.clone())),
+                (None, true) => Some(Cloze::Note),
+                (None, false) => None,
+            };
+            let options = AnkiOptions {
+                cloze,
+                decks: cli.anki_decks,
+                written_at: Utc::now().naive_utc(),
+            };
+            to_apkg(books, &options)
+        }
+        _ => unreachable!("{format:?} holds a single book"),
+    }
+}
+
+/// Writes to `output_dir/name`, creating the directories on the way, or to stdout without
+/// an output directory. Names already in `outputs` get a suffix instead of overwriting the
+/// earlier book. Returns the path of the written file.
+fn write_output(
+    output_dir: Option<&Path>,
+    name: &Path,
+    data: &[u8],
+    outputs: &mut Vec<PathBuf>,
+) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let Some(output_dir) = output_dir else {
+        io::stdout().write_all(data)?;
+        return Ok(None);
+    };
+
+    let path = unique_path(&output_dir.join(name), outputs);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, data)?;
+    outputs.push(path.clone());
+    Ok(Some(path))
+}
+
+fn run_command(command: Command) {
+    match command {
+        Command::Import {
+            library: path,
+            input_files,
+        } => {
+            let mut library = Library::open(&path).unwrap_or_else(|e| fail(&path, e));
+            let mut failures = 0;
+            for input in &input_files {
+                let files = match expand_input(input) {
+                    Ok(files) => files,
+                    Err(e) => {
+                        eprintln!("error: {}: {e}", input.display());
+                        failures += 1;
+                        continue;
+                    }
+                };
+                for input_file in files {
+                    let result = read_boox(&input_file)
+                        .and_then(|boox| Ok((library.import(&boox)?, boox.metadata.title)));
+                    match result {
+                        Ok((Some(stats), title)) => println!(
+                            "{title}: {} added, {} updated, {} unchanged, {} removed",
+                            stats.added, stats.updated, stats.unchanged, stats.removed
+                        ),
+                        Ok((None, title)) => {
+                            println!("{title}: skipped, the library has a newer export");
+                        }
+                        Err(e) => {
+                            eprintln!("error: {}: {e}", input_file.display());
+                            failures += 1;
+                        }
+                    }
+                }
+            }
+            if failures > 0 {
+                process::exit(1);
+            }
+        }
+        Command::Search {
+            library: path,
+            query,
+            fts,
+            limit,
+        } => {
+            let library = Library::open_existing(&path).unwrap_or_else(|e| fail(&path, e));
+            let query = query.join(" ");
+            let query = if fts { query } else { quote_query(&query) };
+            let hits = library
+                .search(&query, limit)
+                .unwrap_or_else(|e| fail(&path, e));
+            for hit in hits {
+                let chapter = hit.chapter.map(|c| format!(", {c}")).unwrap_or_default();
+                println!(
+                    "{} ({}){chapter}, page {}",
+                    hit.title, hit.authors, hit.page
+                );
+                println!("> {}\n", hit.snippet.replace('\n', "\n> "));
+            }
+        }
+    }
+}
+
+/// Reports an error that ends the whole run and exits with a failure code
+fn fail(path: &Path, error: Box<dyn Error>) -> ! {
+    eprintln!("error: {}: {error}", path.display());
+    process::exit(1);
+}
+
+/// Reads an export, json or cache file into a `BooxFile`
+fn read_boox(input_file: &Path) -> Result<BooxFile, Box<dyn Error>> {
+    let data = fs::read(input_file)?;
+
+    // Caches and json already carry the provenance of the export they were parsed from
+    if is_binary_cache(&data) {
+        return from_binary(&data);
+    }
+
+    let data = String::from_utf8(data)?;
+    let mut boox = if is_json(&data) {
+        parse_boox_json(&data)?
+    } else {
+        parse_boox(&data).map_err(|e| e.to_string())?.1
+    };
+    boox.provenance
+        .get_or_insert_with(|| Provenance::new(Some(input_file), &data));
+    Ok(boox)
+}
+
 #[test]
 fn boox_test_v2() {
     let data = include_str!("../test/data/russian_1.txt");
@@ -100,9 +678,9 @@ fn boox_test_v1() {
     let boox = parse_boox(data);
     let md = boox.map(|b| DisplayMarkdown(&b.1).to_string());
 
-    assert_eq!(md, Ok("# One Up on Wall Street - Peter Lynch & John Rothchild (952)
+    assert_eq!(md, Ok("# One Up on Wall Street
 
-**Author:** Peter Lynch; John Rothchild
+**Author:** Peter Lynch, John Rothchild
 
 ---
 
@@ -128,3 +706,19 @@ some very good annotation
 
 ".to_owned()));
 }
+
+#[test]
+fn read_boox_provenance_test() {
+    let export = Path::new("test/data/data.txt");
+    let boox = read_boox(export).unwrap();
+    let file_hash = &boox.provenance.as_ref().unwrap().file_hash;
+
+    let json = std::env::temp_dir().join(format!("read_boox_test_{}.json", process::id()));
+    fs::write(&json, serialize::to_json(&boox, false).unwrap()).unwrap();
+    let reread = read_boox(&json).unwrap();
+    fs::remove_file(&json).unwrap();
+
+    let provenance = reread.provenance.unwrap();
+    assert_eq!(provenance.source_path.as_deref(), Some(export));
+    assert_eq!(&provenance.file_hash, file_hash);
+}
@@ -1,28 +1,92 @@
+use std::path::PathBuf;
+
 use chrono::NaiveDateTime;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Author {
+    pub first_name: String,
+    pub last_name: String,
+}
 
-#[derive(Debug, PartialEq, Eq, Serialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Metadata {
+    /// Title with library IDs and duplicated author suffixes removed
     pub title: String,
-    pub author: String,
+    pub authors: Vec<Author>,
+    /// Title exactly as it appears in the export
+    pub raw_title: String,
+    /// Author line exactly as it appears in the export
+    pub raw_author: String,
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize)]
+/// Location of a parsed element in the original export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    /// Byte offset of the first byte
+    pub start: usize,
+    /// Byte offset one past the last byte
+    pub end: usize,
+    /// 1-based line of the first byte
+    pub start_line: usize,
+    /// 1-based line of the last byte
+    pub end_line: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Highlight {
+    /// Content based ID, see `highlight_id`
+    #[serde(default)]
+    pub id: String,
     pub timestamp: NaiveDateTime,
     pub page: u32,
     pub highlight: String,
     pub note: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note_span: Option<Span>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chapter {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Section {
     HL(Highlight),
-    Chapter(String),
+    Chapter(Chapter),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    V1,
+    V2,
+    Json,
+    Other,
+}
+
+/// Where a parsed file came from, so that separate exports can be told apart
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub source_path: Option<PathBuf>,
+    /// Hex encoded SHA-256 of the input
+    pub file_hash: String,
+    pub format: Format,
+    pub locale: Option<String>,
+    /// Taken from the `annotation-YYYY-MM-DD_HH_MM_SS` suffix Boox adds to file names
+    pub exported_at: Option<NaiveDateTime>,
+    pub tool_version: String,
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BooxFile {
     pub metadata: Metadata,
     pub sections: Vec<Section>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
 }
@@ -0,0 +1,228 @@
+//! # `outline`
+//!
+//! OPML and Roam Research JSON, the two import formats most outliners accept.
+//! Chapters become the top level nodes, highlights their children and notes the children of
+//! their highlight. Highlights before the first chapter sit at the top level.
+
+use std::fmt::{Display, Formatter, Result};
+
+use serde_json::{json, Value};
+
+use crate::{
+    model::{BooxFile, Highlight, Section},
+    text::escape_html,
+};
+
+/// A chapter, or the highlights before the first one
+struct Node<'a> {
+    title: Option<&'a str>,
+    highlights: Vec<&'a Highlight>,
+}
+
+fn nodes(boox: &BooxFile) -> Vec<Node<'_>> {
+    let mut nodes = vec![Node {
+        title: None,
+        highlights: vec![],
+    }];
+    for section in &boox.sections {
+        match section {
+            Section::Chapter(c) => nodes.push(Node {
+                title: Some(&c.title),
+                highlights: vec![],
+            }),
+            Section::HL(highlight) => nodes
+                .last_mut()
+                .expect("starts with the top level")
+                .highlights
+                .push(highlight),
+        }
+    }
+    nodes
+}
+
+pub struct Opml<'a>(pub &'a BooxFile);
+
+impl Display for Opml<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let boox = self.0;
+
+        writeln!(f, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(f, r#"<opml version="2.0">"#)?;
+        writeln!(f, "  <head>")?;
+        writeln!(
+            f,
+            "    <title>{}</title>",
+            escape_html(&boox.metadata.title)
+        )?;
+        if !boox.metadata.authors.is_empty() {
+            writeln!(
+                f,
+                "    <ownerName>{}</ownerName>",
+                escape_html(&boox.metadata.author_names())
+            )?;
+        }
+        writeln!(f, "  </head>")?;
+        writeln!(f, "  <body>")?;
+
+        for node in nodes(boox) {
+            match node.title {
+                Some(title) => {
+                    writeln!(
+                        f,
+                        r#"    <outline text="{}" type="chapter">"#,
+                        attribute(title)
+                    )?;
+                    for highlight in node.highlights {
+                        write_opml_highlight(f, 3, highlight)?;
+                    }
+                    writeln!(f, "    </outline>")?;
+                }
+                None => {
+                    for highlight in node.highlights {
+                        write_opml_highlight(f, 2, highlight)?;
+                    }
+                }
+            }
+        }
+
+        writeln!(f, "  </body>")?;
+        writeln!(f, "</opml>")
+    }
+}
+
+fn write_opml_highlight(f: &mut Formatter, depth: usize, highlight: &Highlight) -> Result {
+    let indent = "  ".repeat(depth);
+    write!(
+        f,
+        r#"{indent}<outline text="{}" type="highlight" page="{}" timestamp="{}" id="{}""#,
+        attribute(&highlight.highlight),
+        highlight.page,
+        highlight.timestamp.format("%Y-%m-%dT%H:%M:%S"),
+        highlight.id
+    )?;
+
+    match &highlight.note {
+        Some(note) => {
+            writeln!(f, ">")?;
+            writeln!(
+                f,
+                r#"{indent}  <outline text="{}" type="note"/>"#,
+                attribute(note)
+            )?;
+            writeln!(f, "{indent}</outline>")
+        }
+        None => writeln!(f, "/>"),
+    }
+}
+
+/// Escapes an attribute value, keeping line breaks that parsers would otherwise turn into spaces
+fn attribute(s: &str) -> String {
+    escape_html(s.trim_end()).replace('\n', "&#10;")
+}
+
+/// A Roam Research import file with one page for the book
+pub fn to_roam_json(boox: &BooxFile) -> Value {
+    let mut children = vec![];
+    if !boox.metadata.authors.is_empty() {
+        let authors: Vec<String> = boox
+            .metadata
+            .authors
+            .iter()
+            .map(|a| format!("[[{a}]]"))
+            .collect();
+        children.push(json!({ "string": format!("Author:: {}", authors.join(", ")) }));
+    }
+
+    for node in nodes(boox) {
+        let highlights = node.highlights.into_iter().map(roam_block);
+        match node.title {
+            Some(title) => children.push(json!({
+                "string": title,
+                "heading": 2,
+                "children": highlights.collect::<Vec<_>>(),
+            })),
+            None => children.extend(highlights),
+        }
+    }
+
+    json!([{
+        "title": boox.metadata.title,
+        "children": children,
+    }])
+}
+
+fn roam_block(highlight: &Highlight) -> Value {
+    let created = highlight.timestamp.and_utc().timestamp_millis();
+    let mut block = json!({
+        "string": highlight.highlight.trim_end(),
+        "create-time": created,
+    });
+
+    if let Some(note) = &highlight.note {
+        block["children"] = json!([{
+            "string": note.trim_end(),
+            "create-time": created,
+        }]);
+    }
+
+    block
+}
+
+#[test]
+fn opml_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+
+    assert_eq!(
+        Opml(&boox).to_string(),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<opml version="2.0">
+  <head>
+    <title>Building a Second Brain -- A Proven Method</title>
+    <ownerName>Tiago Forte</ownerName>
+  </head>
+  <body>
+    <outline text="PKM—or personal knowledge management" type="highlight" page="6" timestamp="2023-04-03T00:41:00" id="30aa3585d76d8ebd"/>
+    <outline text="Chapter 3: How a Second Brain Works" type="chapter">
+      <outline text="We bookmark articles to read later, but rarely find the time to revisit them again" type="highlight" page="32" timestamp="2023-04-03T01:21:00" id="93d7c1a793812574">
+        <outline text="There&#39;s too many to &#10;actually read them all" type="note"/>
+      </outline>
+      <outline text="In other words, &#10;the jobs that are most likely to stick around are those that involve promoting or defending a particular perspective" type="highlight" page="39" timestamp="2023-04-03T16:57:00" id="f7f47e2cd2873944">
+        <outline text="Not sure about now with LLMs" type="note"/>
+      </outline>
+      <outline text="Multimedia" type="highlight" page="40" timestamp="2023-04-03T17:01:00" id="450649f45bde8387"/>
+    </outline>
+  </body>
+</opml>
+"#
+    );
+}
+
+#[test]
+fn roam_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    let roam = to_roam_json(&boox);
+    let children = roam[0]["children"].as_array().unwrap();
+
+    assert_eq!(
+        roam[0]["title"],
+        "Building a Second Brain -- A Proven Method"
+    );
+    assert_eq!(children.len(), 3);
+    assert_eq!(children[0]["string"], "Author:: [[Tiago Forte]]");
+    assert_eq!(
+        children[1],
+        json!({
+            "string": "PKM—or personal knowledge management",
+            "create-time": 1_680_482_460_000_i64
+        })
+    );
+    assert_eq!(children[2]["heading"], 2);
+    assert_eq!(
+        children[2]["children"][0]["children"][0]["string"],
+        "There's too many to \nactually read them all"
+    );
+}
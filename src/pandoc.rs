@@ -0,0 +1,160 @@
+//! # `pandoc`
+//!
+//! Pandoc JSON AST of a `BooxFile`, to be converted further with `pandoc -f json`.
+//! Chapters become headers, and every highlight a `highlight` div holding a block quote with its
+//! page and time, followed by a `note` div if it has a note. The divs carry the highlight's ID,
+//! page and timestamp as attributes for Pandoc filters.
+
+use serde_json::{json, Value};
+
+use crate::model::{BooxFile, Highlight, Section};
+
+/// Version of the AST, matching Pandoc 3.1 and later
+const API_VERSION: [u32; 3] = [1, 23, 1];
+
+pub fn to_pandoc_json(boox: &BooxFile) -> serde_json::Result<String> {
+    let mut meta = serde_json::Map::new();
+    meta.insert(
+        "title".to_owned(),
+        json!({ "t": "MetaInlines", "c": inlines(&boox.metadata.title) }),
+    );
+    if !boox.metadata.authors.is_empty() {
+        let authors: Vec<Value> = boox
+            .metadata
+            .authors
+            .iter()
+            .map(|a| json!({ "t": "MetaInlines", "c": inlines(&a.to_string()) }))
+            .collect();
+        meta.insert(
+            "author".to_owned(),
+            json!({ "t": "MetaList", "c": authors }),
+        );
+    }
+    if let Some(locale) = boox.provenance.as_ref().and_then(|p| p.locale.as_ref()) {
+        meta.insert("lang".to_owned(), json!({ "t": "MetaString", "c": locale }));
+    }
+
+    let blocks: Vec<Value> = boox
+        .sections
+        .iter()
+        .map(|section| match section {
+            Section::Chapter(c) => json!({
+                "t": "Header",
+                "c": [1, attr("", &[], &[]), inlines(&c.title)],
+            }),
+            Section::HL(highlight) => highlight_block(highlight),
+        })
+        .collect();
+
+    serde_json::to_string(&json!({
+        "pandoc-api-version": API_VERSION,
+        "meta": meta,
+        "blocks": blocks,
+    }))
+}
+
+fn highlight_block(highlight: &Highlight) -> Value {
+    let page = highlight.page.to_string();
+    let timestamp = highlight.timestamp.format("%Y-%m-%dT%H:%M:%S").to_string();
+    let caption = format!(
+        "Page {}, {}",
+        highlight.page,
+        highlight.timestamp.format("%Y-%m-%d %H:%M")
+    );
+
+    let mut content = vec![json!({
+        "t": "BlockQuote",
+        "c": [
+            { "t": "Para", "c": inlines(&highlight.highlight) },
+            { "t": "Para", "c": [{ "t": "Emph", "c": inlines(&caption) }] },
+        ],
+    })];
+    if let Some(note) = &highlight.note {
+        content.push(json!({
+            "t": "Div",
+            "c": [attr("", &["note"], &[]), [{ "t": "Para", "c": inlines(note) }]],
+        }));
+    }
+
+    let id = if highlight.id.is_empty() {
+        String::new()
+    } else {
+        format!("h-{}", highlight.id)
+    };
+    json!({
+        "t": "Div",
+        "c": [
+            attr(&id, &["highlight"], &[("page", &page), ("timestamp", &timestamp)]),
+            content,
+        ],
+    })
+}
+
+/// Pandoc's `Attr`: identifier, classes and key-value pairs
+fn attr(id: &str, classes: &[&str], attributes: &[(&str, &str)]) -> Value {
+    json!([id, classes, attributes])
+}
+
+/// Words separated by spaces, with the line breaks of `text` kept
+fn inlines(text: &str) -> Vec<Value> {
+    let mut inlines = vec![];
+
+    for (i, line) in text.trim().lines().enumerate() {
+        if i > 0 {
+            inlines.push(json!({ "t": "LineBreak" }));
+        }
+        for (j, word) in line.split_whitespace().enumerate() {
+            if j > 0 {
+                inlines.push(json!({ "t": "Space" }));
+            }
+            inlines.push(json!({ "t": "Str", "c": word }));
+        }
+    }
+
+    inlines
+}
+
+#[test]
+fn pandoc_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    let ast: Value = serde_json::from_str(&to_pandoc_json(&boox).unwrap()).unwrap();
+
+    assert_eq!(ast["pandoc-api-version"], json!([1, 23, 1]));
+    assert_eq!(
+        ast["meta"]["author"],
+        json!({ "t": "MetaList", "c": [{ "t": "MetaInlines", "c": [
+            { "t": "Str", "c": "Tiago" }, { "t": "Space" }, { "t": "Str", "c": "Forte" }
+        ] }] })
+    );
+
+    let blocks = ast["blocks"].as_array().unwrap();
+    assert_eq!(blocks.len(), 5);
+    assert_eq!(blocks[1]["t"], "Header");
+    assert_eq!(blocks[1]["c"][2][0], json!({ "t": "Str", "c": "Chapter" }));
+
+    let highlight = &blocks[2]["c"];
+    assert_eq!(
+        highlight[0],
+        json!([
+            "h-93d7c1a793812574",
+            ["highlight"],
+            [["page", "32"], ["timestamp", "2023-04-03T01:21:00"]]
+        ])
+    );
+    assert_eq!(highlight[1][0]["t"], "BlockQuote");
+    assert_eq!(
+        highlight[1][1],
+        json!({ "t": "Div", "c": [["", ["note"], []], [{ "t": "Para", "c": [
+            { "t": "Str", "c": "There's" }, { "t": "Space" },
+            { "t": "Str", "c": "too" }, { "t": "Space" },
+            { "t": "Str", "c": "many" }, { "t": "Space" },
+            { "t": "Str", "c": "to" }, { "t": "LineBreak" },
+            { "t": "Str", "c": "actually" }, { "t": "Space" },
+            { "t": "Str", "c": "read" }, { "t": "Space" },
+            { "t": "Str", "c": "them" }, { "t": "Space" },
+            { "t": "Str", "c": "all" }
+        ] }]] })
+    );
+}
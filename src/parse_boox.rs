@@ -12,7 +12,9 @@ use nom::{
 };
 
 use crate::{
-    model::BooxFile,
+    highlight_id::disambiguate_ids,
+    model::{BooxFile, Format},
+    parse_boox_json::is_json,
     parse_boox_v1::{is_v1, parse_boox_v1},
     parse_boox_v2::parse_boox_v2,
 };
@@ -49,14 +51,49 @@ pub fn parse_timestamp(i: &str) -> IResult<&str, NaiveDateTime, VerboseError<&st
     })
 }
 
-pub fn parse_boox(i: &str) -> IResult<&str, BooxFile, VerboseError<&str>> {
-    if is_v1(i) {
-        parse_boox_v1(i)
+// Header preambles written by the Boox exporter in the languages we have samples for
+const LOCALE_PREAMBLES: &[(&str, &str)] = &[
+    ("Reading Notes", "en"),
+    ("Notizen lesen", "de"),
+    ("Заметки при чтении", "ru"),
+];
+
+#[must_use]
+pub fn detect_format(i: &str) -> Format {
+    if is_json(i) {
+        Format::Json
+    } else if is_v1(i) {
+        Format::V1
+    } else if i.contains("<<") && i.contains(">>") {
+        Format::V2
     } else {
-        parse_boox_v2(i)
+        Format::Other
     }
 }
 
+/// Guesses the export language from the header preamble, e.g. "Reading Notes | <<...>>"
+#[must_use]
+pub fn detect_locale(i: &str) -> Option<String> {
+    let (preamble, _) = i.split_once('|')?;
+    let preamble = preamble.trim_start_matches('\u{feff}').trim();
+
+    LOCALE_PREAMBLES
+        .iter()
+        .find(|(p, _)| *p == preamble)
+        .map(|(_, locale)| (*locale).to_owned())
+}
+
+/// Parses a text export; json input goes through `parse_boox_json` instead
+pub fn parse_boox(i: &str) -> IResult<&str, BooxFile, VerboseError<&str>> {
+    let (rest, mut boox) = match detect_format(i) {
+        Format::V1 => parse_boox_v1(i),
+        Format::V2 | Format::Other | Format::Json => parse_boox_v2(i),
+    }?;
+
+    disambiguate_ids(&mut boox.sections);
+    Ok((rest, boox))
+}
+
 #[test]
 fn all_data_test() {
     use std::fs;
@@ -73,6 +110,37 @@ fn all_data_test() {
     }
 }
 
+#[test]
+fn detect_test() {
+    assert_eq!(
+        detect_format(include_str!("../test/data/v1.txt")),
+        Format::V1
+    );
+    assert_eq!(
+        detect_format(include_str!("../test/data/data.txt")),
+        Format::V2
+    );
+
+    assert_eq!(
+        detect_locale(include_str!("../test/data/data.txt")),
+        Some("en".to_owned())
+    );
+    assert_eq!(
+        detect_locale(include_str!("../test/data/russian_1.txt")),
+        Some("ru".to_owned())
+    );
+    assert_eq!(
+        detect_locale(include_str!(
+            "../test/data/Harari_Eine-kurze-Geschichte-der-Menschheit-annotation-2023-02-21_17_11_42.txt"
+        )),
+        Some("de".to_owned())
+    );
+    assert_eq!(detect_format("{\"metadata\": {}}"), Format::Json);
+    assert_eq!(detect_format("Lesenotizen"), Format::Other);
+
+    assert_eq!(detect_locale("Lesenotizen | <<Title>>Author"), None);
+}
+
 #[test]
 fn timestamp_test() {
     use chrono::NaiveDate;
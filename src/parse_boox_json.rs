@@ -0,0 +1,100 @@
+use std::error::Error;
+
+use serde_json::Value;
+
+use crate::{
+    highlight_id::{disambiguate_ids, highlight_id},
+    json_schema::{migrate, JsonBook},
+    model::{BooxFile, Section},
+};
+
+pub fn is_json(i: &str) -> bool {
+    i.trim_start_matches('\u{feff}')
+        .trim_start()
+        .starts_with('{')
+}
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Reads a `BooxFile` back from the json written with `--json`, migrating older versions.
+/// Unlike the text exports this isn't a nom parser, so errors keep serde's or the migration's
+/// message instead of echoing the input.
+pub fn parse_boox_json(i: &str) -> Result<BooxFile> {
+    let value: Value = serde_json::from_str(i.trim_start_matches('\u{feff}'))?;
+    let book: JsonBook = serde_json::from_value(migrate(value)?)?;
+    let mut boox = BooxFile::from(book);
+
+    // Hand-written json may leave the IDs out
+    for section in &mut boox.sections {
+        if let Section::HL(h) = section {
+            if h.id.is_empty() {
+                h.id = highlight_id(&h.highlight, h.page, &h.timestamp);
+            }
+        }
+    }
+    disambiguate_ids(&mut boox.sections);
+
+    Ok(boox)
+}
+
+#[test]
+fn roundtrip_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    let json = serde_json::to_string(&JsonBook::from(&boox)).unwrap();
+
+    assert_eq!(parse_boox_json(&json).unwrap(), boox);
+}
+
+#[test]
+fn legacy_roundtrip_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    let json = serde_json::to_string(&boox).unwrap();
+
+    assert_eq!(parse_boox_json(&json).unwrap(), boox);
+}
+
+#[test]
+fn missing_id_test() {
+    let json = r#"{
+        "metadata": {
+            "title": "Building a Second Brain",
+            "authors": [{"first_name": "Tiago", "last_name": "Forte"}],
+            "raw_title": "Building a Second Brain",
+            "raw_author": "Tiago Forte"
+        },
+        "sections": [
+            {"HL": {
+                "timestamp": "2023-04-03T00:41:00",
+                "page": 6,
+                "highlight": "PKM—or personal knowledge management",
+                "note": null
+            }}
+        ]
+    }"#;
+
+    let boox = parse_boox_json(json).unwrap();
+    let Section::HL(h) = &boox.sections[0] else {
+        panic!("expected a highlight");
+    };
+
+    assert_eq!(h.id, "30aa3585d76d8ebd");
+}
+
+#[test]
+fn error_message_test() {
+    let error = parse_boox_json("{").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "EOF while parsing an object at line 1 column 1"
+    );
+
+    let error = parse_boox_json(r#"{"schema_version": 99}"#).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "schema_version 99 is newer than the supported version 1"
+    );
+}
@@ -1,15 +1,17 @@
 use nom::{
     bytes::complete::{tag, take_until},
-    combinator::{all_consuming, map},
+    combinator::all_consuming,
     error::{FromExternalError, VerboseError},
     multi::many0,
     sequence::{delimited, preceded, terminated, Tuple},
-    IResult,
+    IResult, Offset,
 };
 
 use crate::{
-    model::{BooxFile, Highlight, Metadata, Section},
+    highlight_id::highlight_id,
+    model::{BooxFile, Highlight, Metadata, Section, Span},
     parse_boox::parse_timestamp,
+    span::with_source_span,
 };
 
 const MARK_TIME: &str = "Time：";
@@ -35,22 +37,16 @@ fn parse_header(i: &str) -> IResult<&str, Metadata, VerboseError<&str>> {
 
     let (i, (title, author)) = (title, author).parse(i)?;
 
-    Ok((
-        i,
-        Metadata {
-            title: title.to_owned(),
-            author: author.to_owned(),
-        },
-    ))
+    Ok((i, Metadata::from_raw(title, author)))
 }
 
-fn parse_highlight(i: &str) -> IResult<&str, Highlight, VerboseError<&str>> {
+fn parse_highlight(input: &str) -> IResult<&str, Highlight, VerboseError<&str>> {
     let mut timestamp = delimited(tag(MARK_TIME), parse_timestamp, take_until(MARK_HIGHLIGHT));
     let mut highlight = preceded(tag(MARK_HIGHLIGHT), take_until(MARK_NOTE));
     let mut note = preceded(tag(MARK_NOTE), take_until(MARK_PAGE));
     let mut page = preceded(tag(MARK_PAGE), take_until(MARK_END));
 
-    let (i, timestamp) = timestamp(i)?;
+    let (i, timestamp) = timestamp(input)?;
     let (i, highlight) = highlight(i)?;
     let (i, note) = note(i)?;
 
@@ -66,49 +62,48 @@ fn parse_highlight(i: &str) -> IResult<&str, Highlight, VerboseError<&str>> {
 
     let (i, _) = (tag(MARK_END), tag("\n")).parse(post_page)?;
 
+    let highlight = highlight.trim();
     let note = note.trim();
-    let note = if note.is_empty() {
-        None
-    } else {
-        Some(note.to_owned())
-    };
+    let note = if note.is_empty() { None } else { Some(note) };
 
     Ok((
         i,
         Highlight {
+            id: highlight_id(highlight, page, &timestamp),
             timestamp,
             page,
-            highlight: highlight.trim().to_owned(),
-            note,
+            highlight: highlight.to_owned(),
+            note: note.map(str::to_owned),
+            span: Some(Span::of(input, &input[..input.offset(i)])),
+            note_span: note.map(|n| Span::of(input, n)),
         },
     ))
 }
 
-fn parse_sectioned_highlight(i: &str) -> IResult<&str, (String, Highlight), VerboseError<&str>> {
+pub fn parse_boox_v1(input: &str) -> IResult<&str, BooxFile, VerboseError<&str>> {
+    // I don't fully understand the sections logic,
+    // so I'm just going to discard the section line before every highlight
     let chapter_line = terminated(take_until("\n"), tag("\n"));
+    let sectioned_highlight = preceded(chapter_line, with_source_span(input, parse_highlight));
 
-    (map(chapter_line, &str::to_owned), parse_highlight).parse(i)
-}
-
-pub fn parse_boox_v1(i: &str) -> IResult<&str, BooxFile, VerboseError<&str>> {
-    let (i, (metadata, sectioned_highlights)) = (
-        parse_header,
-        all_consuming(many0(parse_sectioned_highlight)),
-    )
-        .parse(i)?;
+    let (i, (metadata, highlights)) =
+        (parse_header, all_consuming(many0(sectioned_highlight))).parse(input)?;
 
-    // I don't fully understand the sections logic,
-    // so I'm just going to discard the information
-    let sections = sectioned_highlights
-        .into_iter()
-        .map(|(_, h)| Section::HL(h))
-        .collect();
+    let sections = highlights.into_iter().map(Section::HL).collect();
 
-    Ok((i, BooxFile { metadata, sections }))
+    Ok((
+        i,
+        BooxFile {
+            metadata,
+            sections,
+            provenance: None,
+        },
+    ))
 }
 
 #[test]
 fn boox_test() {
+    use crate::model::Author;
     use chrono::NaiveDate;
 
     let data = include_str!("../test/data/v1.txt");
@@ -121,47 +116,96 @@ fn boox_test() {
             "",
             BooxFile {
                 metadata: Metadata {
-                    title: "One Up on Wall Street - Peter Lynch & John Rothchild (952)".to_owned(),
-                    author: "Peter Lynch; John Rothchild".to_owned()
+                    title: "One Up on Wall Street".to_owned(),
+                    authors: vec![
+                        Author {
+                            first_name: "Peter".to_owned(),
+                            last_name: "Lynch".to_owned()
+                        },
+                        Author {
+                            first_name: "John".to_owned(),
+                            last_name: "Rothchild".to_owned()
+                        },
+                    ],
+                    raw_title: "One Up on Wall Street - Peter Lynch & John Rothchild (952)".to_owned(),
+                    raw_author: "Peter Lynch; John Rothchild".to_owned()
                 },
                 sections: vec![
                     Section::HL(Highlight {
+                        id: "5872b4288673009d".to_owned(),
                         timestamp: NaiveDate::from_ymd_opt(2022, 3, 7)
                             .unwrap()
                             .and_hms_opt(1, 11, 0)
                             .unwrap(),
                         page: 13,
                         highlight: "tics to a degree neither side could have imagined in the doldrums of the early 1970s, when I first took the helm at Magellan. At that low point, demoralized investors had to remind themselves that bear markets don’t last forever, and those with patience held on to their stocks and mutual funds for the fifteen years it took the Dow and other averages to regain the prices reached in the mid-1960s. Today it’s worth reminding ourselves that bull markets don’t last forever and that patience is required in both directions.On  of this book I say the breakup of ATT".to_owned(),
-                        note: Some("some very good annotation".to_owned())
+                        note: Some("some very good annotation".to_owned()),
+                        span: Some(Span {
+                            start: 110,
+                            end: 806,
+                            start_line: 4,
+                            end_line: 8
+                        }),
+                        note_span: Some(Span {
+                            start: 740,
+                            end: 765,
+                            start_line: 6,
+                            end_line: 6
+                        })
                     }),
                     Section::HL(Highlight {
+                        id: "4f6ee239160d4c4a".to_owned(),
                         timestamp: NaiveDate::from_ymd_opt(2022, 3, 7)
                             .unwrap()
                             .and_hms_opt(14, 2, 0)
                             .unwrap(),
                         page: 20,
                         highlight: "valued at $10 billion may not be worth a dime. As expectations turn to reality, the winners will be more obvious than they are today. Investors who see this will have time to act on their “edge.”".to_owned(),
-                        note: None
+                        note: None,
+                        span: Some(Span {
+                            start: 845,
+                            end: 1146,
+                            start_line: 10,
+                            end_line: 14
+                        }),
+                        note_span: None
                     }),
                     Section::HL(Highlight {
+                        id: "6c108175065b1b78".to_owned(),
                         timestamp: NaiveDate::from_ymd_opt(2022, 3, 7)
                             .unwrap()
                             .and_hms_opt(14, 2, 0)
                             .unwrap(),
                         page: 20,
                         highlight: "Microsoft went public in 1986 at 15 cents a share. Three years later you could buy a share for under $1, and from there it advanced eightyfold. (The stock has “split” several times along the way, so original shares never actually sold for 15 cents—for further explanation, see the footnote on .) If you took the Missouri “show me” approach and waited to buy Microsoft until it triumphed with Windows 95, you still made seven times your money. You didn’t have to be a programmer to notice Microsoft everywhere you looked. Except in the Apple orchard, all new computers".to_owned(),
-                        note: None
+                        note: None,
+                        span: Some(Span {
+                            start: 1147,
+                            end: 1828,
+                            start_line: 16,
+                            end_line: 20
+                        }),
+                        note_span: None
                     }),
                     Section::HL(Highlight {
+                        id: "41b7721f5a630cd3".to_owned(),
                         timestamp: NaiveDate::from_ymd_opt(2022, 3, 7)
                             .unwrap()
                             .and_hms_opt(1, 20, 0)
                             .unwrap(),
                         page: 22,
                         highlight: "Street Journal and Barron’s, and get a snapshot review of almost any publicly traded company. From there you can access “Zack’s” and get a summary of ratings from all the analysts who follow a particular stock.Again thanks to the Internet, the cost of buying and selling stocks has been drastically reduced for the small investor, the way it was reduced for institutional investors in 1975. On-line trading has pressured traditional brokerage houses to reduce commissions and transaction fees, continuing a trend that began with the birth of the discount broker two decades ago.You may be wondering what’s happened to my investing habits since I left Magellan. Instead of following thousands".to_owned(),
-                        note: None
+                        note: None,
+                        span: Some(Span {
+                            start: 1867,
+                            end: 2670,
+                            start_line: 22,
+                            end_line: 26
+                        }),
+                        note_span: None
                     }),
-                ]
+                ],
+                provenance: None
             }
         ))
     )
@@ -182,13 +226,26 @@ fn highlight_test() {
         Ok((
             "",
             Highlight {
+                id: "74867b892cbb3c49".to_owned(),
                 timestamp: NaiveDate::from_ymd_opt(2022, 3, 7)
                     .unwrap()
                     .and_hms_opt(1, 11, 0)
                     .unwrap(),
                 page: 13,
                 highlight: "tics to a degree".to_owned(),
-                note: Some("some very good annotation".to_owned())
+                note: Some("some very good annotation".to_owned()),
+                span: Some(Span {
+                    start: 0,
+                    end: 143,
+                    start_line: 1,
+                    end_line: 5
+                }),
+                note_span: Some(Span {
+                    start: 77,
+                    end: 102,
+                    start_line: 3,
+                    end_line: 3
+                })
             }
         ))
     );
@@ -204,6 +261,7 @@ fn highlight_test() {
 
 #[test]
 fn header_test() {
+    use crate::model::Author;
     use nom::{
         error::{ErrorKind::TakeUntil, ParseError, VerboseError},
         Err,
@@ -214,8 +272,19 @@ fn header_test() {
         Ok((
             "",
             Metadata {
-                title: "One Up on Wall Street - Peter Lynch & John Rothchild (952)".to_owned(),
-                author: "Peter Lynch; John Rothchild".to_owned()
+                title: "One Up on Wall Street".to_owned(),
+                authors: vec![
+                    Author {
+                        first_name: "Peter".to_owned(),
+                        last_name: "Lynch".to_owned()
+                    },
+                    Author {
+                        first_name: "John".to_owned(),
+                        last_name: "Rothchild".to_owned()
+                    },
+                ],
+                raw_title: "One Up on Wall Street - Peter Lynch & John Rothchild (952)".to_owned(),
+                raw_author: "Peter Lynch; John Rothchild".to_owned()
             }
         ))
     );
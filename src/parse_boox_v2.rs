@@ -5,13 +5,15 @@ use nom::{
     error::{FromExternalError, VerboseError},
     multi::many0,
     sequence::{delimited, preceded, terminated, Tuple},
-    IResult, Parser,
+    IResult, Offset, Parser,
 };
 
 use crate::{
-    model::{BooxFile, Highlight, Metadata, Section},
+    highlight_id::highlight_id,
+    model::{BooxFile, Chapter, Highlight, Metadata, Section, Span},
     nom_util::take_until_multiple,
     parse_boox::{is_digit, parse_timestamp},
+    span::with_source_span,
 };
 
 const SEP_TEXT: &str = " | ";
@@ -24,16 +26,10 @@ fn parse_header(i: &str) -> IResult<&str, Metadata, VerboseError<&str>> {
 
     let (i, (_, _, title, author)) = (start, sep, title, author).parse(i)?;
 
-    Ok((
-        i,
-        Metadata {
-            title: title.to_owned(),
-            author: author.to_owned(),
-        },
-    ))
+    Ok((i, Metadata::from_raw(title, author)))
 }
 
-fn parse_highlight(i: &str) -> IResult<&str, Highlight, VerboseError<&str>> {
+fn parse_highlight(input: &str) -> IResult<&str, Highlight, VerboseError<&str>> {
     const NOTE_END_MARKER: &str = "-------------------";
     const NOTE_TAG: &str = "【Note】";
     const HIGHLIGHT_END_MARKERS: &[&str; 2] = &[NOTE_TAG, NOTE_END_MARKER];
@@ -42,7 +38,7 @@ fn parse_highlight(i: &str) -> IResult<&str, Highlight, VerboseError<&str>> {
     let mut highlight = take_until_multiple(HIGHLIGHT_END_MARKERS);
     let note = preceded(tag(NOTE_TAG), take_until(NOTE_END_MARKER));
 
-    let (i, timestamp) = parse_timestamp(i)?;
+    let (i, timestamp) = parse_timestamp(input)?;
     let (i, page) = terminated(page, tag("\n")).parse(i).and_then(|(r, m)| {
         let v = m.parse().map_err(|e| {
             nom::Err::Error(nom::error::VerboseError::from_external_error(
@@ -55,16 +51,19 @@ fn parse_highlight(i: &str) -> IResult<&str, Highlight, VerboseError<&str>> {
         Ok((r, v))
     })?;
     let (i, highlight) = highlight(i).map(|(r, m)| (r, m.trim().to_owned()))?;
-    let (i, note) = opt(note)(i).map(|(r, m)| (r, m.map(|s| s.trim().to_owned())))?;
+    let (i, note) = opt(note)(i).map(|(r, m)| (r, m.map(str::trim)))?;
     let (i, _) = (tag(NOTE_END_MARKER), opt(tag("\n"))).parse(i)?;
 
     Ok((
         i,
         Highlight {
+            id: highlight_id(&highlight, page, &timestamp),
             timestamp,
             page,
             highlight,
-            note,
+            note: note.map(str::to_owned),
+            span: Some(Span::of(input, &input[..input.offset(i)])),
+            note_span: note.map(|n| Span::of(input, n)),
         },
     ))
 }
@@ -72,24 +71,37 @@ fn parse_highlight(i: &str) -> IResult<&str, Highlight, VerboseError<&str>> {
 fn parse_highlight_or_chapter(i: &str) -> IResult<&str, Section, VerboseError<&str>> {
     let chapter_line = terminated(take_until("\n"), tag("\n"));
 
+    let chapter = map(chapter_line, |title: &str| Chapter {
+        title: title.to_owned(),
+        span: Some(Span::of(i, title)),
+    });
+
     alt((
         map(parse_highlight, Section::HL),
-        map(map(chapter_line, &str::to_owned), Section::Chapter),
+        map(chapter, Section::Chapter),
     ))(i)
 }
 
-pub fn parse_boox_v2(i: &str) -> IResult<&str, BooxFile, VerboseError<&str>> {
+pub fn parse_boox_v2(input: &str) -> IResult<&str, BooxFile, VerboseError<&str>> {
     let (i, (metadata, sections)) = (
         parse_header,
-        all_consuming(many0(parse_highlight_or_chapter)),
+        all_consuming(many0(with_source_span(input, parse_highlight_or_chapter))),
     )
-        .parse(i)?;
+        .parse(input)?;
 
-    Ok((i, BooxFile { metadata, sections }))
+    Ok((
+        i,
+        BooxFile {
+            metadata,
+            sections,
+            provenance: None,
+        },
+    ))
 }
 
 #[test]
 fn boox_test() {
+    use crate::model::Author;
     use chrono::NaiveDate;
 
     let data = include_str!("../test/data/data.txt");
@@ -103,47 +115,105 @@ fn boox_test() {
             BooxFile {
                 metadata: Metadata {
                     title: "Building a Second Brain -- A Proven Method".to_owned(),
-                    author: "Tiago Forte".to_owned()
+                    authors: vec![
+                        Author {
+                            first_name: "Tiago".to_owned(),
+                            last_name: "Forte".to_owned()
+                        },
+                    ],
+                    raw_title: "Building a Second Brain -- A Proven Method".to_owned(),
+                    raw_author: "Tiago Forte".to_owned()
                 },
                 sections: vec![
                     Section::HL(Highlight {
+                        id: "30aa3585d76d8ebd".to_owned(),
                         timestamp: NaiveDate::from_ymd_opt(2023, 4, 3)
                             .unwrap()
                             .and_hms_opt(0, 41, 0)
                             .unwrap(),
                         page: 6,
                         highlight: "PKM—or personal knowledge management".to_owned(),
-                        note: None
+                        note: None,
+                        span: Some(Span {
+                            start: 76,
+                            end: 172,
+                            start_line: 2,
+                            end_line: 4
+                        }),
+                        note_span: None
+                    }),
+                    Section::Chapter(Chapter {
+                        title: "Chapter 3: How a Second Brain Works".to_string(),
+                        span: Some(Span {
+                            start: 172,
+                            end: 207,
+                            start_line: 5,
+                            end_line: 5
+                        })
                     }),
-                    Section::Chapter("Chapter 3: How a Second Brain Works".to_string()),
                     Section::HL(Highlight {
+                        id: "93d7c1a793812574".to_owned(),
                         timestamp: NaiveDate::from_ymd_opt(2023, 4, 3)
                             .unwrap()
                             .and_hms_opt(1, 21, 0)
                             .unwrap(),
                         page: 32,
                         highlight: "We bookmark articles to read later, but rarely find the time to revisit them again".to_owned(),
-                        note: Some("There's too many to \nactually read them all".to_owned())
+                        note: Some("There's too many to \nactually read them all".to_owned()),
+                        span: Some(Span {
+                            start: 208,
+                            end: 403,
+                            start_line: 6,
+                            end_line: 10
+                        }),
+                        note_span: Some(Span {
+                            start: 339,
+                            end: 382,
+                            start_line: 8,
+                            end_line: 9
+                        })
                     }),
                     Section::HL(Highlight {
+                        id: "f7f47e2cd2873944".to_owned(),
                         timestamp: NaiveDate::from_ymd_opt(2023, 4, 3)
                             .unwrap()
                             .and_hms_opt(16, 57, 0)
                             .unwrap(),
                         page: 39,
                         highlight: "In other words, \nthe jobs that are most likely to stick around are those that involve promoting or defending a particular perspective".to_owned(),
-                        note: Some("Not sure about now with LLMs".to_owned())
+                        note: Some("Not sure about now with LLMs".to_owned()),
+                        span: Some(Span {
+                            start: 403,
+                            end: 634,
+                            start_line: 11,
+                            end_line: 15
+                        }),
+                        note_span: Some(Span {
+                            start: 585,
+                            end: 613,
+                            start_line: 14,
+                            end_line: 14
+                        })
                     }),
                     Section::HL(Highlight {
+                        id: "450649f45bde8387".to_owned(),
                         timestamp: NaiveDate::from_ymd_opt(2023, 4, 3)
                             .unwrap()
                             .and_hms_opt(17, 1, 0)
                             .unwrap(),
                         page: 40,
                         highlight: "Multimedia".to_owned(),
-                        note: None
+                        note: None,
+                        span: Some(Span {
+                            start: 634,
+                            end: 703,
+                            start_line: 16,
+                            end_line: 18
+                        }),
+                        note_span: None
                     }),
-                ]
+                ],
+                provenance: None
             }
         ))
     )
@@ -160,13 +230,26 @@ fn section_test() {
         Ok((
             "",
             Section::HL(Highlight {
+                id: "598f166959ce1b7f".to_owned(),
                 timestamp: NaiveDate::from_ymd_opt(2023, 4, 3)
                     .unwrap()
                     .and_hms_opt(1, 21, 0)
                     .unwrap(),
                 page: 32,
                 highlight: "We bookmark articles to read later".to_owned(),
-                note: Some("There's too many".to_owned())
+                note: Some("There's too many".to_owned()),
+                span: Some(Span {
+                    start: 0,
+                    end: 120,
+                    start_line: 1,
+                    end_line: 4
+                }),
+                note_span: Some(Span {
+                    start: 83,
+                    end: 99,
+                    start_line: 3,
+                    end_line: 3
+                })
             })
         ))
     );
@@ -175,7 +258,15 @@ fn section_test() {
         parse_highlight_or_chapter("Chapter 3: How a Second Brain Works\n"),
         Ok((
             "",
-            Section::Chapter("Chapter 3: How a Second Brain Works".to_string())
+            Section::Chapter(Chapter {
+                title: "Chapter 3: How a Second Brain Works".to_string(),
+                span: Some(Span {
+                    start: 0,
+                    end: 35,
+                    start_line: 1,
+                    end_line: 1
+                })
+            })
         ))
     );
 }
@@ -195,13 +286,26 @@ fn highlight_test() {
         Ok((
             "",
             Highlight {
+                id: "598f166959ce1b7f".to_owned(),
                 timestamp: NaiveDate::from_ymd_opt(2023, 4, 3)
                     .unwrap()
                     .and_hms_opt(1, 21, 0)
                     .unwrap(),
                 page: 32,
                 highlight: "We bookmark articles to read later".to_owned(),
-                note: Some("There's too many".to_owned())
+                note: Some("There's too many".to_owned()),
+                span: Some(Span {
+                    start: 0,
+                    end: 120,
+                    start_line: 1,
+                    end_line: 4
+                }),
+                note_span: Some(Span {
+                    start: 83,
+                    end: 99,
+                    start_line: 3,
+                    end_line: 3
+                })
             }
         ))
     );
@@ -213,13 +317,26 @@ fn highlight_test() {
         Ok((
             "",
             Highlight {
+                id: "598f166959ce1b7f".to_owned(),
                 timestamp: NaiveDate::from_ymd_opt(2023, 4, 3)
                     .unwrap()
                     .and_hms_opt(1, 21, 0)
                     .unwrap(),
                 page: 32,
                 highlight: "We bookmark articles to read later".to_owned(),
-                note: Some("There's too many".to_owned())
+                note: Some("There's too many".to_owned()),
+                span: Some(Span {
+                    start: 0,
+                    end: 119,
+                    start_line: 1,
+                    end_line: 4
+                }),
+                note_span: Some(Span {
+                    start: 83,
+                    end: 99,
+                    start_line: 3,
+                    end_line: 3
+                })
             }
         ))
     );
@@ -243,6 +360,7 @@ fn highlight_test() {
 
 #[test]
 fn header_test() {
+    use crate::model::Author;
     use nom::{
         error::{ErrorKind::TakeUntil, ParseError, VerboseError},
         Err,
@@ -254,7 +372,12 @@ fn header_test() {
             "",
             Metadata {
                 title: "Building a Second Brain -- A Proven Method".to_owned(),
-                author: "Tiago Forte".to_owned()
+                authors: vec![Author {
+                    first_name: "Tiago".to_owned(),
+                    last_name: "Forte".to_owned()
+                },],
+                raw_title: "Building a Second Brain -- A Proven Method".to_owned(),
+                raw_author: "Tiago Forte".to_owned()
             }
         ))
     );
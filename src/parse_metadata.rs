@@ -0,0 +1,326 @@
+//! # `parse_metadata`
+//!
+//! Turns the raw title and author strings from an export header into structured metadata
+
+use std::fmt::Display;
+
+use crate::model::{Author, Metadata};
+
+const AUTHOR_SEPARATORS: &[char] = &[';', '&'];
+const TITLE_AUTHOR_SEPARATORS: &[&str] = &[" - ", " -- ", " – ", " — "];
+
+impl Metadata {
+    pub fn from_raw(title: &str, author: &str) -> Self {
+        let authors = parse_authors(author);
+
+        Self {
+            title: clean_title(title, &authors),
+            authors,
+            raw_title: title.to_owned(),
+            raw_author: author.to_owned(),
+        }
+    }
+
+    /// All author names joined for display, e.g. "Peter Lynch, John Rothchild"
+    pub fn author_names(&self) -> String {
+        self.authors
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl Display for Author {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.first_name.is_empty(), self.last_name.is_empty()) {
+            (true, _) => write!(f, "{}", self.last_name),
+            (false, true) => write!(f, "{}", self.first_name),
+            (false, false) => write!(f, "{} {}", self.first_name, self.last_name),
+        }
+    }
+}
+
+/// Splits an author line like "Peter Lynch; John Rothchild" or "Harari, Yuval Noah"
+pub fn parse_authors(i: &str) -> Vec<Author> {
+    let shouting = is_shouting(i);
+
+    i.split(AUTHOR_SEPARATORS)
+        .flat_map(|part| part.split(" and "))
+        .flat_map(|part| split_comma_list(part, shouting))
+        .collect()
+}
+
+// Author lines in all capitals ("LYNCH, PETER") are common in library metadata. Lines with any
+// lowercase letters are kept as they are, so initials like "J.R.R." aren't mistaken for shouting.
+fn is_shouting(i: &str) -> bool {
+    i.chars().filter(|c| c.is_alphabetic()).count() > 1 && !i.chars().any(char::is_lowercase)
+}
+
+// "Last, First" is a single author, while "First Last, First Last" is a list
+fn split_comma_list(i: &str, shouting: bool) -> Vec<Author> {
+    let pieces: Vec<&str> = i.split(',').map(str::trim).collect();
+    let words = |s: &str| s.split_whitespace().count();
+
+    match pieces.as_slice() {
+        [last, first]
+            if words(last) == 1 || words(first) == 1 || last.starts_with(char::is_lowercase) =>
+        {
+            let name = |s: &str| {
+                s.split_whitespace()
+                    .map(|w| normalise_case(w, shouting))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            vec![Author {
+                first_name: name(first),
+                last_name: name(last),
+            }]
+        }
+        _ => pieces
+            .into_iter()
+            .filter_map(|name| parse_author(name, shouting))
+            .collect(),
+    }
+}
+
+// The last word is the last name, together with particles like "van" or "de" before it
+fn parse_author(name: &str, shouting: bool) -> Option<Author> {
+    let words: Vec<String> = name
+        .split_whitespace()
+        .map(|w| normalise_case(w, shouting))
+        .collect();
+    let (last, first) = words.split_last()?;
+    let particles = first
+        .iter()
+        .rev()
+        .take_while(|w| w.starts_with(char::is_lowercase))
+        .count();
+    let (first, particles) = first.split_at(first.len() - particles);
+
+    Some(Author {
+        first_name: first.join(" "),
+        last_name: particles
+            .iter()
+            .chain([last])
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" "),
+    })
+}
+
+// "JEAN-PAUL" -> "Jean-Paul", "O'BRIEN" -> "O'Brien"
+fn normalise_case(word: &str, shouting: bool) -> String {
+    if !shouting {
+        return word.to_owned();
+    }
+
+    let mut capitalise = true;
+    let mut out = String::with_capacity(word.len());
+    for c in word.chars() {
+        if capitalise {
+            out.extend(c.to_uppercase());
+        } else {
+            out.extend(c.to_lowercase());
+        }
+        capitalise = matches!(c, '-' | '.' | '\'' | '’');
+    }
+    out
+}
+
+/// Removes library IDs, file name artifacts and repeated author names from a title
+pub fn clean_title(i: &str, authors: &[Author]) -> String {
+    let title = i.trim();
+    let title = if is_filename_like(title) {
+        title_from_filename(title, authors)
+    } else {
+        title.to_owned()
+    };
+    let title = strip_library_id(&title);
+    let title = strip_author_suffix(title, authors);
+
+    if title.is_empty() {
+        i.trim().to_owned()
+    } else {
+        title.to_owned()
+    }
+}
+
+fn is_filename_like(title: &str) -> bool {
+    !title.contains(char::is_whitespace) && (title.contains('_') || title.matches('-').count() > 1)
+}
+
+// "Harari_Eine-kurze-Geschichte-der-Menschheit" -> "Eine kurze Geschichte der Menschheit"
+fn title_from_filename(title: &str, authors: &[Author]) -> String {
+    let segments: Vec<&str> = title.split('_').collect();
+    let skip = segments
+        .iter()
+        .take_while(|s| is_author_last_name(s, authors))
+        .count()
+        .min(segments.len() - 1);
+
+    segments[skip..]
+        .join(" ")
+        .split('-')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn is_author_last_name(s: &str, authors: &[Author]) -> bool {
+    authors
+        .iter()
+        .any(|a| !a.last_name.is_empty() && a.last_name.to_lowercase() == s.to_lowercase())
+}
+
+// "One Up on Wall Street (952)" -> "One Up on Wall Street"
+fn strip_library_id(title: &str) -> &str {
+    let title = title.trim_end();
+
+    for (open, close) in [('(', ')'), ('[', ']')] {
+        let Some(inner) = title.strip_suffix(close) else {
+            continue;
+        };
+        let Some(start) = inner.rfind(open) else {
+            continue;
+        };
+        let id = &inner[start + open.len_utf8()..];
+
+        if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) {
+            return inner[..start].trim_end();
+        }
+    }
+
+    title
+}
+
+// "One Up on Wall Street - Peter Lynch & John Rothchild" -> "One Up on Wall Street"
+fn strip_author_suffix<'a>(title: &'a str, authors: &[Author]) -> &'a str {
+    TITLE_AUTHOR_SEPARATORS
+        .iter()
+        .filter_map(|sep| title.rfind(sep).map(|idx| (idx, &title[idx + sep.len()..])))
+        .find(|(_, suffix)| {
+            let suffix_authors = parse_authors(suffix);
+            !suffix_authors.is_empty()
+                && suffix_authors
+                    .iter()
+                    .all(|a| is_author_last_name(&a.last_name, authors))
+        })
+        .map_or(title, |(idx, _)| title[..idx].trim_end())
+}
+
+#[test]
+fn authors_test() {
+    let author = |first: &str, last: &str| Author {
+        first_name: first.to_owned(),
+        last_name: last.to_owned(),
+    };
+
+    assert_eq!(
+        parse_authors("Peter Lynch; John Rothchild"),
+        vec![author("Peter", "Lynch"), author("John", "Rothchild")]
+    );
+    assert_eq!(
+        parse_authors("Harari, Yuval Noah"),
+        vec![author("Yuval Noah", "Harari")]
+    );
+    assert_eq!(
+        parse_authors("Peter Lynch, John Rothchild"),
+        vec![author("Peter", "Lynch"), author("John", "Rothchild")]
+    );
+    assert_eq!(
+        parse_authors("Роберт Сесил Мартин"),
+        vec![author("Роберт Сесил", "Мартин")]
+    );
+    assert_eq!(
+        parse_authors("LYNCH, PETER"),
+        vec![author("Peter", "Lynch")]
+    );
+    assert_eq!(parse_authors(""), vec![]);
+}
+
+#[test]
+fn author_case_test() {
+    let author = |first: &str, last: &str| Author {
+        first_name: first.to_owned(),
+        last_name: last.to_owned(),
+    };
+
+    assert_eq!(
+        parse_authors("J.R.R. Tolkien"),
+        vec![author("J.R.R.", "Tolkien")]
+    );
+    assert_eq!(
+        parse_authors("J.R.R. TOLKIEN"),
+        vec![author("J.R.R.", "Tolkien")]
+    );
+    assert_eq!(
+        parse_authors("JEAN-PAUL SARTRE"),
+        vec![author("Jean-Paul", "Sartre")]
+    );
+    assert_eq!(
+        parse_authors("O'BRIEN, FLANN"),
+        vec![author("Flann", "O'Brien")]
+    );
+    assert_eq!(
+        parse_authors("Jean-Paul SARTRE"),
+        vec![author("Jean-Paul", "SARTRE")]
+    );
+}
+
+#[test]
+fn multi_word_last_name_test() {
+    let author = |first: &str, last: &str| Author {
+        first_name: first.to_owned(),
+        last_name: last.to_owned(),
+    };
+
+    assert_eq!(
+        parse_authors("van Gogh, Vincent"),
+        vec![author("Vincent", "van Gogh")]
+    );
+    assert_eq!(
+        parse_authors("Vincent van Gogh"),
+        vec![author("Vincent", "van Gogh")]
+    );
+    assert_eq!(
+        parse_authors("García Márquez, Gabriel"),
+        vec![author("Gabriel", "García Márquez")]
+    );
+    assert_eq!(
+        parse_authors("Ludwig van Beethoven; Simone de Beauvoir"),
+        vec![
+            author("Ludwig", "van Beethoven"),
+            author("Simone", "de Beauvoir")
+        ]
+    );
+}
+
+#[test]
+fn title_test() {
+    let metadata = |title: &str, author: &str| Metadata::from_raw(title, author).title;
+
+    assert_eq!(
+        metadata(
+            "One Up on Wall Street - Peter Lynch & John Rothchild (952)",
+            "Peter Lynch; John Rothchild"
+        ),
+        "One Up on Wall Street"
+    );
+    assert_eq!(
+        metadata(
+            "Harari_Eine-kurze-Geschichte-der-Menschheit",
+            "Harari, Yuval Noah"
+        ),
+        "Eine kurze Geschichte der Menschheit"
+    );
+    assert_eq!(
+        metadata("Building a Second Brain -- A Proven Method", "Tiago Forte"),
+        "Building a Second Brain -- A Proven Method"
+    );
+    assert_eq!(
+        metadata("Self-Reliance", "Ralph Waldo Emerson"),
+        "Self-Reliance"
+    );
+    assert_eq!(metadata("(952)", ""), "(952)");
+}
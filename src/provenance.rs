@@ -0,0 +1,110 @@
+//! # `provenance`
+//!
+//! Records where a `BooxFile` came from: the input file, its hash and the export it belongs to
+
+use std::{fmt::Display, path::Path};
+
+use chrono::NaiveDateTime;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    display_markdown::{yaml_string, AsMarkdown},
+    model::{BooxFile, Format, Provenance},
+    parse_boox::{detect_format, detect_locale},
+};
+
+const EXPORT_MARKER: &str = "annotation-";
+const EXPORT_TIMESTAMP_FORMAT: &str = "%Y-%m-%d_%H_%M_%S";
+
+impl Provenance {
+    pub fn new(source_path: Option<&Path>, data: &str) -> Self {
+        Self {
+            source_path: source_path.map(Path::to_path_buf),
+            file_hash: format!("{:x}", Sha256::digest(data.as_bytes())),
+            format: detect_format(data),
+            locale: detect_locale(data),
+            exported_at: source_path.and_then(parse_export_timestamp),
+            tool_version: env!("CARGO_PKG_VERSION").to_owned(),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::V1 => write!(f, "v1"),
+            Self::V2 => write!(f, "v2"),
+            Self::Json => write!(f, "json"),
+            Self::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// Reads the export time from names like `Title-annotation-2023-02-21_17_11_42.txt`
+pub fn parse_export_timestamp(path: &Path) -> Option<NaiveDateTime> {
+    let stem = path.file_stem()?.to_str()?;
+    let (_, timestamp) = stem.rsplit_once(EXPORT_MARKER)?;
+
+    NaiveDateTime::parse_from_str(timestamp, EXPORT_TIMESTAMP_FORMAT).ok()
+}
+
+/// YAML front matter with the provenance block, to be placed before the Markdown body
+pub struct FrontMatter<'a>(pub &'a BooxFile);
+
+impl AsMarkdown for FrontMatter<'_> {
+    fn fmt_markdown(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let Some(provenance) = &self.0.provenance else {
+            return Ok(());
+        };
+
+        writeln!(f, "---")?;
+        if let Some(path) = &provenance.source_path {
+            writeln!(f, "source: {}", yaml_string(&path.to_string_lossy())?)?;
+        }
+        writeln!(f, "sha256: {}", provenance.file_hash)?;
+        writeln!(f, "format: {}", provenance.format)?;
+        if let Some(locale) = &provenance.locale {
+            writeln!(f, "locale: {locale}")?;
+        }
+        if let Some(exported_at) = &provenance.exported_at {
+            writeln!(f, "exported: {}", exported_at.format("%Y-%m-%dT%H:%M:%S"))?;
+        }
+        writeln!(
+            f,
+            "tool_version: {}",
+            yaml_string(&provenance.tool_version)?
+        )?;
+        writeln!(f, "---\n")
+    }
+}
+
+#[test]
+fn export_timestamp_test() {
+    use chrono::NaiveDate;
+
+    assert_eq!(
+        parse_export_timestamp(Path::new(
+            "test/data/Harari_Eine-kurze-Geschichte-der-Menschheit-annotation-2023-02-21_17_11_42.txt"
+        )),
+        Some(
+            NaiveDate::from_ymd_opt(2023, 2, 21)
+                .unwrap()
+                .and_hms_opt(17, 11, 42)
+                .unwrap()
+        )
+    );
+    assert_eq!(parse_export_timestamp(Path::new("test/data/v1.txt")), None);
+}
+
+#[test]
+fn provenance_test() {
+    let provenance = Provenance::new(Some(Path::new("test/data/v1.txt")), "");
+
+    assert_eq!(
+        provenance.file_hash,
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    assert_eq!(provenance.format, Format::Other);
+    assert_eq!(provenance.locale, None);
+    assert_eq!(provenance.exported_at, None);
+}
@@ -0,0 +1,69 @@
+//! # `script`
+//!
+//! Which writing systems a book uses, for renderers that need to pick fonts or packages
+
+use crate::model::{BooxFile, Section};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Scripts {
+    pub cyrillic: bool,
+    /// Chinese, Japanese or Korean
+    pub cjk: bool,
+}
+
+impl Scripts {
+    pub fn of(boox: &BooxFile) -> Self {
+        let mut scripts = Self::default();
+        let texts = boox.sections.iter().flat_map(|s| match s {
+            Section::HL(h) => vec![h.highlight.as_str(), h.note.as_deref().unwrap_or_default()],
+            Section::Chapter(c) => vec![c.title.as_str()],
+        });
+
+        for c in [boox.metadata.title.as_str(), &boox.metadata.author_names()]
+            .into_iter()
+            .chain(texts)
+            .flat_map(str::chars)
+        {
+            scripts.cyrillic |= is_cyrillic(c);
+            scripts.cjk |= is_cjk(c);
+        }
+
+        scripts
+    }
+}
+
+const fn is_cyrillic(c: char) -> bool {
+    matches!(c, '\u{0400}'..='\u{052F}')
+}
+
+const fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{11FF}' // Hangul Jamo
+        | '\u{3040}'..='\u{30FF}' // Hiragana, Katakana
+        | '\u{3400}'..='\u{4DBF}' // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK Unified Ideographs
+        | '\u{AC00}'..='\u{D7AF}' // Hangul Syllables
+        | '\u{F900}'..='\u{FAFF}' // CJK Compatibility Ideographs
+        | '\u{20000}'..='\u{2FA1F}' // CJK Extensions B and later
+    )
+}
+
+#[test]
+fn scripts_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/russian_1.txt")).unwrap();
+    assert_eq!(
+        Scripts::of(&boox),
+        Scripts {
+            cyrillic: true,
+            cjk: false
+        }
+    );
+
+    let (_, mut boox) = parse_boox(include_str!("../test/data/v1.txt")).unwrap();
+    assert_eq!(Scripts::of(&boox), Scripts::default());
+
+    boox.metadata.title = "ウォール街で勝つ法則".to_owned();
+    assert!(Scripts::of(&boox).cjk);
+}
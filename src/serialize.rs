@@ -0,0 +1,232 @@
+//! # `serialize`
+//!
+//! Writes the versioned json schema types in the various serde data formats
+
+use std::error::Error;
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    json_schema::{JsonBook, JsonHighlight, JsonMetadata, JsonSection},
+    model::{BooxFile, Section},
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// One line of the JSON Lines output: a highlight with the book metadata repeated
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    schema_version: u64,
+    #[serde(flatten)]
+    metadata: &'a JsonMetadata,
+    chapter: Option<&'a str>,
+    #[serde(flatten)]
+    highlight: &'a JsonHighlight,
+}
+
+/// Column sets of the CSV output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum CsvProfile {
+    /// Every field of a highlight
+    #[default]
+    Full,
+    /// The columns of Readwise's CSV import
+    Readwise,
+}
+
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    title: &'a str,
+    author: &'a str,
+    chapter: Option<&'a str>,
+    page: u32,
+    timestamp: String,
+    highlight: &'a str,
+    note: Option<&'a str>,
+    id: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct ReadwiseRow<'a> {
+    highlight: &'a str,
+    title: &'a str,
+    author: &'a str,
+    #[serde(rename = "URL")]
+    url: Option<&'a str>,
+    note: Option<&'a str>,
+    location: u32,
+    date: String,
+}
+
+pub fn to_json(boox: &BooxFile, pretty: bool) -> Result<String> {
+    let book = JsonBook::from(boox);
+
+    Ok(if pretty {
+        serde_json::to_string_pretty(&book)?
+    } else {
+        serde_json::to_string(&book)?
+    })
+}
+
+/// One flattened highlight per line, so the output can be streamed into line based tools
+pub fn to_json_lines(boox: &BooxFile) -> Result<String> {
+    let book = JsonBook::from(boox);
+    let mut chapter = None;
+    let mut out = String::new();
+
+    for section in &book.sections {
+        match section {
+            JsonSection::Chapter(c) => chapter = Some(c.title.as_str()),
+            JsonSection::Highlight(highlight) => {
+                let line = JsonLine {
+                    schema_version: book.schema_version,
+                    metadata: &book.metadata,
+                    chapter,
+                    highlight,
+                };
+                out.push_str(&serde_json::to_string(&line)?);
+                out.push('\n');
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+pub fn to_yaml(boox: &BooxFile) -> Result<String> {
+    Ok(serde_yaml::to_string(&JsonBook::from(boox))?)
+}
+
+pub fn to_toml(boox: &BooxFile) -> Result<String> {
+    Ok(toml::to_string(&JsonBook::from(boox))?)
+}
+
+/// One row per highlight, with `delimiter` between fields (`b','` or `b'\t'`).
+/// Fields containing line breaks are quoted, as are fields containing the delimiter or quotes.
+pub fn to_csv(boox: &BooxFile, delimiter: u8, profile: CsvProfile) -> Result<String> {
+    let author = boox.metadata.author_names();
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(vec![]);
+    let mut chapter = None;
+
+    for section in &boox.sections {
+        let highlight = match section {
+            Section::Chapter(c) => {
+                chapter = Some(c.title.as_str());
+                continue;
+            }
+            Section::HL(highlight) => highlight,
+        };
+
+        match profile {
+            CsvProfile::Full => writer.serialize(CsvRow {
+                title: &boox.metadata.title,
+                author: &author,
+                chapter,
+                page: highlight.page,
+                timestamp: highlight.timestamp.format("%Y-%m-%dT%H:%M:%S").to_string(),
+                highlight: &highlight.highlight,
+                note: highlight.note.as_deref(),
+                id: &highlight.id,
+            })?,
+            CsvProfile::Readwise => writer.serialize(ReadwiseRow {
+                highlight: &highlight.highlight,
+                title: &boox.metadata.title,
+                author: &author,
+                url: None,
+                note: highlight.note.as_deref(),
+                location: highlight.page,
+                date: highlight.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+            })?,
+        }
+    }
+
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+#[test]
+fn json_lines_test() {
+    use crate::parse_boox::parse_boox;
+    use serde_json::Value;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    let lines = to_json_lines(&boox).unwrap();
+    let lines: Vec<Value> = lines
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+
+    assert_eq!(lines.len(), 4);
+    assert_eq!(
+        lines[0]["title"],
+        "Building a Second Brain -- A Proven Method"
+    );
+    assert_eq!(lines[0]["chapter"], Value::Null);
+    assert_eq!(lines[0]["text"], "PKM—or personal knowledge management");
+    assert_eq!(lines[1]["chapter"], "Chapter 3: How a Second Brain Works");
+    assert_eq!(lines[1]["page"], 32);
+}
+
+#[test]
+fn formats_test() {
+    use crate::{parse_boox::parse_boox, parse_boox_json::parse_boox_json};
+
+    let (_, boox) = parse_boox(include_str!("../test/data/v1.txt")).unwrap();
+
+    let pretty = to_json(&boox, true).unwrap();
+    assert!(pretty.lines().count() > 1);
+    assert_eq!(parse_boox_json(&pretty).unwrap(), boox);
+
+    let (_, boox) = parse_boox(include_str!("../test/data/v1.txt")).unwrap();
+
+    let yaml = to_yaml(&boox).unwrap();
+    assert!(yaml.contains("title: One Up on Wall Street\n"));
+    assert!(yaml.contains("- type: highlight\n"));
+
+    let toml = to_toml(&boox).unwrap();
+    assert!(toml.contains("schema_version = 1\n"));
+    assert!(toml.contains("[[sections]]\ntype = \"highlight\"\n"));
+}
+
+#[test]
+fn csv_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+
+    let csv = to_csv(&boox, b',', CsvProfile::Full).unwrap();
+    assert!(csv.starts_with(
+        "title,author,chapter,page,timestamp,highlight,note,id
+Building a Second Brain -- A Proven Method,Tiago Forte,,6,2023-04-03T00:41:00,PKM—or personal knowledge management,,30aa3585d76d8ebd
+"
+    ));
+    assert!(csv.contains(
+        ",32,2023-04-03T01:21:00,\
+        \"We bookmark articles to read later, but rarely find the time to revisit them again\",\
+        \"There's too many to \nactually read them all\",93d7c1a793812574\n"
+    ));
+
+    let readwise = to_csv(&boox, b'\t', CsvProfile::Readwise).unwrap();
+    let mut rows = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .from_reader(readwise.as_bytes());
+    assert_eq!(
+        rows.headers().unwrap(),
+        vec![
+            "Highlight",
+            "Title",
+            "Author",
+            "URL",
+            "Note",
+            "Location",
+            "Date"
+        ]
+    );
+    let rows: Vec<_> = rows.records().map(|r| r.unwrap()).collect();
+    assert_eq!(rows.len(), 4);
+    assert_eq!(&rows[1][4], "There's too many to \nactually read them all");
+    assert_eq!(&rows[1][6], "2023-04-03 01:21:00");
+}
@@ -0,0 +1,218 @@
+//! # `slug`
+//!
+//! URL and anchor friendly versions of titles, and file names that are safe on every platform
+
+use std::path::{Path, PathBuf};
+
+use deunicode::deunicode;
+
+/// Room for a collision suffix and the extension within the 255 bytes most file systems allow
+pub const MAX_FILE_NAME_LEN: usize = 200;
+const MAX_EXTENSION_LEN: usize = 10;
+const FALLBACK_NAME: &str = "untitled";
+/// Device names that Windows won't open as files, with or without an extension
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Lowercases `s` and joins its alphanumeric runs with dashes, e.g. "Chapter 3: How" -> "chapter-3-how".
+/// Letters outside ASCII are kept as they are.
+pub fn slugify(s: &str) -> String {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Makes a single path component out of `name`, e.g. "Either/Or: A Fragment" -> "Either-Or - A Fragment".
+/// Characters that file systems reject are replaced or dropped, leading dots are removed so that
+/// `..` can't climb out of the output directory, reserved device names get a `_` and long names
+/// are shortened, keeping the extension. With `ascii`, letters are transliterated to ASCII first,
+/// so "Чистая архитектура" becomes "Chistaia arkhitektura".
+pub fn safe_file_name(name: &str, ascii: bool) -> String {
+    let name = if ascii {
+        deunicode(name)
+    } else {
+        name.to_owned()
+    };
+
+    let mut replaced = String::new();
+    for c in name.chars() {
+        match c {
+            '/' | '\\' | '|' => replaced.push('-'),
+            ':' => replaced.push_str(" - "),
+            '"' => replaced.push('\''),
+            '*' | '?' | '<' | '>' => {}
+            c if c.is_whitespace() => replaced.push(' '),
+            c if c.is_control() => {}
+            c => replaced.push(c),
+        }
+    }
+    let replaced = replaced
+        .split(' ')
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>();
+    let name = replaced.join(" ");
+    let name = name
+        .trim_start_matches(['.', ' '])
+        .trim_end_matches(['.', ' ']);
+
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension))
+            if !stem.is_empty()
+                && !extension.is_empty()
+                && extension.len() <= MAX_EXTENSION_LEN
+                && extension.chars().all(|c| c.is_ascii_alphanumeric()) =>
+        {
+            (stem, Some(extension))
+        }
+        _ => (name, None),
+    };
+
+    let max_stem_len = MAX_FILE_NAME_LEN - extension.map_or(0, |e| e.len() + 1);
+    let mut stem = truncate(stem, max_stem_len)
+        .trim_end_matches(['.', ' '])
+        .to_owned();
+    if stem.is_empty() {
+        stem = FALLBACK_NAME.to_owned();
+    }
+    // Windows ignores everything from the first dot and trailing spaces when looking for devices,
+    // so `CON.tar.gz` needs the `_` right after `CON`
+    let device = stem.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES
+        .iter()
+        .any(|r| r.eq_ignore_ascii_case(device))
+    {
+        stem.insert(device.len(), '_');
+    }
+
+    match extension {
+        Some(extension) => format!("{stem}.{extension}"),
+        None => stem,
+    }
+}
+
+/// Makes a relative path out of `path`, with `/` separating directories and every component
+/// passed through `safe_file_name`. Empty, `.` and `..` components are dropped.
+pub fn safe_path(path: &str, ascii: bool) -> PathBuf {
+    path.split('/')
+        .filter(|c| !c.trim_matches(['.', ' ']).is_empty())
+        .map(|c| safe_file_name(c, ascii))
+        .collect()
+}
+
+/// `path`, or the first of `path (2)`, `path (3)`... that isn't in `used`. Paths are compared
+/// case-insensitively, as they would clash on case-insensitive file systems.
+pub fn unique_path(path: &Path, used: &[PathBuf]) -> PathBuf {
+    let is_used = |p: &Path| {
+        let p = p.to_string_lossy().to_lowercase();
+        used.iter().any(|u| u.to_string_lossy().to_lowercase() == p)
+    };
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut unique = path.to_path_buf();
+    let mut n = 1;
+    while is_used(&unique) {
+        n += 1;
+        let mut name = format!("{stem} ({n})");
+        if let Some(extension) = path.extension() {
+            name = format!("{name}.{}", extension.to_string_lossy());
+        }
+        unique.set_file_name(name);
+    }
+    unique
+}
+
+/// The longest prefix of `s` of at most `len` bytes that ends on a character boundary
+fn truncate(s: &str, len: usize) -> &str {
+    let mut end = len.min(s.len());
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+#[test]
+fn slugify_test() {
+    assert_eq!(
+        slugify("Chapter 3: How a Second Brain Works"),
+        "chapter-3-how-a-second-brain-works"
+    );
+    assert_eq!(
+        slugify("  --Building a Second Brain -- "),
+        "building-a-second-brain"
+    );
+    assert_eq!(slugify("Чистая архитектура"), "чистая-архитектура");
+    assert_eq!(slugify("?!"), "");
+}
+
+#[test]
+fn safe_file_name_test() {
+    assert_eq!(
+        safe_file_name(
+            "One Up on Wall Street - Peter Lynch & John Rothchild (952).md",
+            false
+        ),
+        "One Up on Wall Street - Peter Lynch & John Rothchild (952).md"
+    );
+    assert_eq!(
+        safe_file_name("Either/Or: A \"Fragment\" of Life?", false),
+        "Either-Or - A 'Fragment' of Life"
+    );
+    assert_eq!(
+        safe_file_name("Чистая архитектура.md", false),
+        "Чистая архитектура.md"
+    );
+    assert_eq!(
+        safe_file_name("Чистая архитектура.md", true),
+        "Chistaia arkhitektura.md"
+    );
+    assert_eq!(safe_file_name("三体", true), "San Ti");
+    assert_eq!(safe_file_name("..", false), "untitled");
+    assert_eq!(safe_file_name("../../etc/passwd", false), "-..-etc-passwd");
+    assert_eq!(safe_file_name(".md", false), "md");
+    assert_eq!(safe_file_name("con.md", false), "con_.md");
+    assert_eq!(safe_file_name("Nul", false), "Nul_");
+    assert_eq!(safe_file_name("CON.tar.gz", false), "CON_.tar.gz");
+    assert_eq!(safe_file_name("aux .notes.md", false), "aux_ .notes.md");
+    assert_eq!(safe_file_name("Line\nbreak\t.", false), "Line break");
+
+    let long = safe_file_name(&format!("{}.md", "Дом ".repeat(100)), false);
+    assert!(long.len() <= MAX_FILE_NAME_LEN);
+    assert!(long.starts_with("Дом Дом") && long.ends_with("Дом.md"));
+}
+
+#[test]
+fn safe_path_test() {
+    assert_eq!(
+        safe_path("Peter Lynch/One Up: Wall Street.md", false),
+        Path::new("Peter Lynch/One Up - Wall Street.md")
+    );
+    assert_eq!(
+        safe_path("/../Роберт Мартин/./Чистая.md", true),
+        Path::new("Robert Martin/Chistaia.md")
+    );
+}
+
+#[test]
+fn unique_path_test() {
+    let used = vec![
+        PathBuf::from("out/Book.md"),
+        PathBuf::from("out/book (2).md"),
+    ];
+
+    assert_eq!(
+        unique_path(Path::new("out/Other.md"), &used),
+        Path::new("out/Other.md")
+    );
+    assert_eq!(
+        unique_path(Path::new("out/BOOK.md"), &used),
+        Path::new("out/BOOK (3).md")
+    );
+    assert_eq!(
+        unique_path(Path::new("out/Book"), &[PathBuf::from("out/Book")]),
+        Path::new("out/Book (2)")
+    );
+}
@@ -0,0 +1,167 @@
+//! # `span`
+//!
+//! Helpers to record where parsed elements were found in the original export
+
+use nom::{IResult, Offset, Parser};
+
+use crate::model::{BooxFile, Highlight, Section, Span};
+
+impl Span {
+    /// Span of `part` within `input`, where `part` must be a subslice of `input`.
+    /// Only the input up to the end of `part` is scanned for line breaks.
+    pub fn of(input: &str, part: &str) -> Self {
+        let end = input.offset(part) + part.len();
+        LineIndex::new(&input[..end]).span(input, part)
+    }
+
+    /// Moves a span found in a slice to the slice's own position in the enclosing input
+    #[must_use]
+    pub const fn shift(self, by: Self) -> Self {
+        Self {
+            start: self.start + by.start,
+            end: self.end + by.start,
+            start_line: self.start_line + by.start_line - 1,
+            end_line: self.end_line + by.start_line - 1,
+        }
+    }
+}
+
+/// The offsets at which the lines of an input start, so that the line of every element of a
+/// parse can be looked up without counting line breaks from the start each time
+pub struct LineIndex {
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(input: &str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(input.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { starts }
+    }
+
+    /// The line, counted from 1, of the byte at `offset`
+    pub fn line(&self, offset: usize) -> usize {
+        self.starts.partition_point(|&start| start <= offset)
+    }
+
+    /// Span of `part` within `input`, the input this index was built from
+    pub fn span(&self, input: &str, part: &str) -> Span {
+        let start = input.offset(part);
+        let end = start + part.len();
+
+        Span {
+            start,
+            end,
+            start_line: self.line(start),
+            end_line: self.line(end.saturating_sub(1).max(start)),
+        }
+    }
+}
+
+/// Elements whose spans can be moved from slice-relative to input-relative positions
+pub trait ShiftSpan {
+    #[must_use]
+    fn shift_span(self, by: Span) -> Self;
+}
+
+impl ShiftSpan for Highlight {
+    fn shift_span(mut self, by: Span) -> Self {
+        self.span = self.span.map(|s| s.shift(by));
+        self.note_span = self.note_span.map(|s| s.shift(by));
+        self
+    }
+}
+
+impl ShiftSpan for Section {
+    fn shift_span(self, by: Span) -> Self {
+        match self {
+            Self::HL(h) => Self::HL(h.shift_span(by)),
+            Self::Chapter(mut c) => {
+                c.span = c.span.map(|s| s.shift(by));
+                Self::Chapter(c)
+            }
+        }
+    }
+}
+
+/// Runs `parser` on a slice of `origin` and makes the spans it records relative to `origin`
+pub fn with_source_span<'a, O, E, P>(
+    origin: &'a str,
+    mut parser: P,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O, E>
+where
+    O: ShiftSpan,
+    P: Parser<&'a str, O, E>,
+{
+    let lines = LineIndex::new(origin);
+
+    move |i| {
+        let (rest, o) = parser.parse(i)?;
+
+        Ok((rest, o.shift_span(lines.span(origin, i))))
+    }
+}
+
+impl Section {
+    pub fn clear_spans(&mut self) {
+        match self {
+            Self::HL(h) => {
+                h.span = None;
+                h.note_span = None;
+            }
+            Self::Chapter(c) => c.span = None,
+        }
+    }
+}
+
+impl BooxFile {
+    /// Drops all source spans, e.g. to keep them out of serialized output
+    pub fn clear_spans(&mut self) {
+        self.sections.iter_mut().for_each(Section::clear_spans);
+    }
+}
+
+#[test]
+fn span_test() {
+    let input = "first\nsecond line\nthird";
+
+    assert_eq!(
+        Span::of(input, &input[6..17]),
+        Span {
+            start: 6,
+            end: 17,
+            start_line: 2,
+            end_line: 2
+        }
+    );
+    assert_eq!(
+        Span::of(input, &input[6..]),
+        Span {
+            start: 6,
+            end: 23,
+            start_line: 2,
+            end_line: 3
+        }
+    );
+    assert_eq!(
+        Span::of(input, &input[6..18]),
+        Span {
+            start: 6,
+            end: 18,
+            start_line: 2,
+            end_line: 2
+        }
+    );
+
+    let lines = LineIndex::new(input);
+    assert_eq!(lines.line(0), 1);
+    assert_eq!(lines.line(5), 1);
+    assert_eq!(lines.line(6), 2);
+    assert_eq!(lines.line(23), 3);
+    assert_eq!(lines.span(input, &input[6..]), Span::of(input, &input[6..]));
+
+    let inner = Span::of(input, &input[18..]).shift(Span::of(input, &input[6..]));
+    assert_eq!(inner.start, 24);
+    assert_eq!(inner.start_line, 4);
+}
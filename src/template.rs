@@ -0,0 +1,200 @@
+//! # `template`
+//!
+//! Renders a `BooxFile` through a user supplied Jinja-style template.
+//!
+//! Templates see the following variables:
+//! - `title`, `author` (all names joined), `authors`, `metadata` and `provenance`
+//! - `sections`: chapters and highlights in reading order, told apart by their `type`
+//! - `chapters`: highlights grouped by chapter, the first group has no title if the book
+//!   starts with highlights before any chapter
+//! - `highlights`: every highlight with its `chapter`, and `notes`: those that have a note
+//! - `block_ids`: whether `--block-ids` was passed
+//! - `citation_key`: the key to cite the book with if `--cite` was passed
+//!
+//! On top of the minijinja builtins there are the `date(format)`, `quote` and `slugify` filters.
+
+use std::fmt::Write;
+
+use chrono::NaiveDateTime;
+use minijinja::{Environment, Error, ErrorKind};
+use serde::Serialize;
+
+use crate::{
+    display_markdown::MarkdownOptions,
+    json_schema::{JsonAuthor, JsonBook, JsonHighlight, JsonMetadata, JsonProvenance, JsonSection},
+    model::BooxFile,
+    slug::slugify,
+};
+
+/// The built-in Markdown layout, as a starting point for custom templates
+pub const DEFAULT_TEMPLATE: &str = include_str!("../templates/default.md.jinja");
+
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+#[derive(Serialize)]
+struct TemplateContext<'a> {
+    title: &'a str,
+    author: String,
+    authors: &'a [JsonAuthor],
+    metadata: &'a JsonMetadata,
+    provenance: Option<&'a JsonProvenance>,
+    sections: &'a [JsonSection],
+    chapters: Vec<ChapterContext<'a>>,
+    highlights: Vec<HighlightContext<'a>>,
+    notes: Vec<HighlightContext<'a>>,
+    block_ids: bool,
+    citation_key: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct ChapterContext<'a> {
+    title: Option<&'a str>,
+    highlights: Vec<HighlightContext<'a>>,
+}
+
+#[derive(Clone, Serialize)]
+struct HighlightContext<'a> {
+    chapter: Option<&'a str>,
+    #[serde(flatten)]
+    highlight: &'a JsonHighlight,
+}
+
+pub fn render_template(
+    boox: &BooxFile,
+    template: &str,
+    options: MarkdownOptions,
+) -> Result<String, Error> {
+    let book = JsonBook::from(boox);
+
+    let mut chapters = vec![ChapterContext {
+        title: None,
+        highlights: vec![],
+    }];
+    for section in &book.sections {
+        match section {
+            JsonSection::Chapter(c) => chapters.push(ChapterContext {
+                title: Some(&c.title),
+                highlights: vec![],
+            }),
+            JsonSection::Highlight(highlight) => {
+                let chapter = chapters.last_mut().expect("starts with one chapter");
+                chapter.highlights.push(HighlightContext {
+                    chapter: chapter.title,
+                    highlight,
+                });
+            }
+        }
+    }
+    if chapters[0].highlights.is_empty() {
+        chapters.remove(0);
+    }
+
+    let highlights: Vec<_> = chapters
+        .iter()
+        .flat_map(|c| c.highlights.iter().cloned())
+        .collect();
+    let notes = highlights
+        .iter()
+        .filter(|h| h.highlight.note.is_some())
+        .cloned()
+        .collect();
+
+    let context = TemplateContext {
+        title: &book.metadata.title,
+        author: boox.metadata.author_names(),
+        authors: &book.metadata.authors,
+        metadata: &book.metadata,
+        provenance: book.provenance.as_ref(),
+        sections: &book.sections,
+        chapters,
+        highlights,
+        notes,
+        block_ids: options.block_ids,
+        citation_key: options.citation_key,
+    };
+
+    environment().render_str(template, context)
+}
+
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    env.add_filter("date", date);
+    env.add_filter("quote", quote);
+    env.add_filter("slugify", |s: String| slugify(&s));
+    env
+}
+
+/// Formats an ISO timestamp from the model with a chrono format string
+fn date(value: String, format: Option<String>) -> Result<String, Error> {
+    let timestamp = NaiveDateTime::parse_from_str(&value, TIMESTAMP_FORMAT)
+        .map_err(|e| Error::new(ErrorKind::InvalidOperation, format!("not a timestamp: {e}")))?;
+
+    let mut out = String::new();
+    write!(
+        out,
+        "{}",
+        timestamp.format(format.as_deref().unwrap_or(DEFAULT_DATE_FORMAT))
+    )
+    .map_err(|_| Error::new(ErrorKind::InvalidOperation, "invalid date format"))?;
+
+    Ok(out)
+}
+
+/// Turns text into a Markdown blockquote, one `> ` per line
+fn quote(value: String) -> String {
+    value
+        .lines()
+        .map(|l| format!("> {l}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn default_template_test() {
+    use crate::{
+        display_markdown::{BooxMarkdown, DisplayMarkdown},
+        parse_boox::parse_boox,
+    };
+
+    for data in [
+        include_str!("../test/data/data.txt"),
+        include_str!("../test/data/v1.txt"),
+        include_str!("../test/data/russian_1.txt"),
+    ] {
+        let (_, boox) = parse_boox(data).unwrap();
+
+        for (block_ids, citation_key) in [(false, None), (true, Some("forte2022"))] {
+            let options = MarkdownOptions {
+                block_ids,
+                citation_key,
+            };
+
+            assert_eq!(
+                render_template(&boox, DEFAULT_TEMPLATE, options).unwrap(),
+                DisplayMarkdown(&BooxMarkdown(&boox, options)).to_string()
+            );
+        }
+    }
+}
+
+#[test]
+fn custom_template_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    let template =
+        "{% for c in chapters %}{{ (c.title or 'Introduction') | slugify }}: {{ c.highlights | length }}\n{% endfor %}\
+        {% for n in notes %}{{ n.timestamp | date('%d.%m.%Y') }} {{ n.chapter }}\n{% endfor %}";
+
+    assert_eq!(
+        render_template(&boox, template, MarkdownOptions::default()).unwrap(),
+        "introduction: 1\nchapter-3-how-a-second-brain-works: 3\n\
+        03.04.2023 Chapter 3: How a Second Brain Works\n\
+        03.04.2023 Chapter 3: How a Second Brain Works\n"
+    );
+
+    assert!(render_template(&boox, "{{ title | date }}", MarkdownOptions::default()).is_err());
+}
@@ -0,0 +1,87 @@
+//! # `text`
+//!
+//! Helpers for the text of titles, highlights and notes that several outputs share
+
+/// Joins the words of `s` with single spaces, turning line breaks and runs of whitespace into one
+pub fn normalise_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Collects `#tags` in order of first use, without the `#`.
+/// Like Obsidian, purely numeric tags such as `#1` are not tags.
+pub fn extract_tags<'a>(texts: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let is_tag_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '/');
+    let mut tags: Vec<String> = vec![];
+
+    for text in texts {
+        let mut rest = text;
+        while let Some(idx) = rest.find('#') {
+            let preceded_by_word = rest[..idx].chars().next_back().is_some_and(is_tag_char);
+            let candidate = &rest[idx + 1..];
+            let len = candidate
+                .find(|c| !is_tag_char(c))
+                .unwrap_or(candidate.len());
+            let tag = &candidate[..len];
+
+            if !preceded_by_word
+                && !tag.is_empty()
+                && !tag.chars().all(|c| c.is_ascii_digit())
+                && !tags.iter().any(|t| t == tag)
+            {
+                tags.push(tag.to_owned());
+            }
+
+            rest = &candidate[len..];
+        }
+    }
+
+    tags
+}
+
+/// Escapes text for HTML and XML, in element content as well as attribute values
+pub fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[test]
+fn normalise_whitespace_test() {
+    assert_eq!(
+        normalise_whitespace(" In other words, \nthe jobs\t that"),
+        "In other words, the jobs that"
+    );
+    assert_eq!(normalise_whitespace("\n"), "");
+}
+
+#[test]
+fn tags_test() {
+    assert_eq!(
+        extract_tags(
+            [
+                "#investing is #long-term",
+                "issue#3, #1 and #investing/stocks",
+                "#investing again"
+            ]
+            .into_iter()
+        ),
+        vec!["investing", "long-term", "investing/stocks"]
+    );
+}
+
+#[test]
+fn escape_html_test() {
+    assert_eq!(
+        escape_html("<b>\"AT&T\"</b> isn't"),
+        "&lt;b&gt;&quot;AT&amp;T&quot;&lt;/b&gt; isn&#39;t"
+    );
+}
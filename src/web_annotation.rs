@@ -0,0 +1,150 @@
+//! # `web_annotation`
+//!
+//! The highlights as a W3C Web Annotation collection in JSON-LD. Every highlight targets the book
+//! with the quoted text and its page, and notes become textual bodies.
+//! The book is identified by its ISBN if known, and by an ID derived from its metadata otherwise.
+
+use chrono::{FixedOffset, NaiveDateTime, SecondsFormat};
+use serde_json::{json, Value};
+
+use crate::{
+    highlight_id::book_id,
+    model::{BooxFile, Highlight, Section},
+};
+
+const CONTEXT: &str = "http://www.w3.org/ns/anno.jsonld";
+/// PDF fragment identifiers, which have the `page=` syntax for page selectors
+const PAGE_FRAGMENT_SPEC: &str = "http://tools.ietf.org/rfc/rfc3778";
+const URN_PREFIX: &str = "urn:highlight-extract";
+
+/// `timezone` is the offset of the device clock, without which the times are left local
+pub fn to_web_annotation(
+    boox: &BooxFile,
+    isbn: Option<&str>,
+    timezone: Option<FixedOffset>,
+) -> Value {
+    let book_id = book_id(&boox.metadata);
+    let source = match isbn {
+        Some(isbn) => format!("urn:isbn:{}", isbn.replace(['-', ' '], "")),
+        None => format!("{URN_PREFIX}:book:{book_id}"),
+    };
+
+    let items: Vec<Value> = boox
+        .sections
+        .iter()
+        .filter_map(|s| match s {
+            Section::HL(highlight) => Some(annotation(highlight, &source, timezone)),
+            Section::Chapter(_) => None,
+        })
+        .collect();
+
+    json!({
+        "@context": CONTEXT,
+        "id": format!("{URN_PREFIX}:collection:{book_id}"),
+        "type": "AnnotationCollection",
+        "label": boox.metadata.title,
+        "total": items.len(),
+        "first": {
+            "type": "AnnotationPage",
+            "startIndex": 0,
+            "items": items,
+        },
+    })
+}
+
+fn annotation(highlight: &Highlight, source: &str, timezone: Option<FixedOffset>) -> Value {
+    let mut annotation = json!({
+        "id": format!("{URN_PREFIX}:highlight:{}", highlight.id),
+        "type": "Annotation",
+        "motivation": "highlighting",
+        "created": created(highlight.timestamp, timezone),
+        "target": {
+            "source": source,
+            "selector": [
+                {
+                    "type": "TextQuoteSelector",
+                    "exact": highlight.highlight,
+                },
+                {
+                    "type": "FragmentSelector",
+                    "conformsTo": PAGE_FRAGMENT_SPEC,
+                    "value": format!("page={}", highlight.page),
+                },
+            ],
+        },
+    });
+
+    if let Some(note) = &highlight.note {
+        annotation["motivation"] = json!("commenting");
+        annotation["body"] = json!({
+            "type": "TextualBody",
+            "purpose": "commenting",
+            "format": "text/plain",
+            "value": note,
+        });
+    }
+
+    annotation
+}
+
+// The device records the local time of its clock, which can only be given an offset when told
+fn created(timestamp: NaiveDateTime, timezone: Option<FixedOffset>) -> String {
+    match timezone.and_then(|tz| timestamp.and_local_timezone(tz).single()) {
+        Some(time) => time.to_rfc3339_opts(SecondsFormat::Secs, true),
+        None => timestamp.format("%Y-%m-%dT%H:%M:%S").to_string(),
+    }
+}
+
+#[test]
+fn web_annotation_test() {
+    use crate::parse_boox::parse_boox;
+
+    let (_, boox) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    let collection = to_web_annotation(&boox, Some("978-0-593-29881-6"), None);
+
+    assert_eq!(collection["type"], "AnnotationCollection");
+    assert_eq!(collection["total"], 4);
+    assert_eq!(
+        collection["first"]["items"][1],
+        json!({
+            "id": "urn:highlight-extract:highlight:93d7c1a793812574",
+            "type": "Annotation",
+            "motivation": "commenting",
+            "created": "2023-04-03T01:21:00",
+            "body": {
+                "type": "TextualBody",
+                "purpose": "commenting",
+                "format": "text/plain",
+                "value": "There's too many to \nactually read them all"
+            },
+            "target": {
+                "source": "urn:isbn:9780593298816",
+                "selector": [
+                    {
+                        "type": "TextQuoteSelector",
+                        "exact": "We bookmark articles to read later, but rarely find the time to revisit them again"
+                    },
+                    {
+                        "type": "FragmentSelector",
+                        "conformsTo": "http://tools.ietf.org/rfc/rfc3778",
+                        "value": "page=32"
+                    }
+                ]
+            }
+        })
+    );
+    assert!(collection["first"]["items"][0].get("body").is_none());
+
+    let berlin = "+02:00".parse().unwrap();
+    let collection = to_web_annotation(&boox, None, Some(berlin));
+    assert_eq!(
+        collection["first"]["items"][1]["created"],
+        "2023-04-03T01:21:00+02:00"
+    );
+    let utc = "+00:00".parse().unwrap();
+    let collection = to_web_annotation(&boox, None, Some(utc));
+    assert_eq!(
+        collection["first"]["items"][1]["created"],
+        "2023-04-03T01:21:00Z"
+    );
+}
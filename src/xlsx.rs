@@ -0,0 +1,290 @@
+//! # `xlsx`
+//!
+//! Excel workbooks with a row per highlight, either on a sheet per book or all on one sheet
+//! with the book in the first columns. Timestamps are real dates and pages numbers, so they
+//! sort and filter properly, and the header row is frozen with an autofilter.
+
+use std::error::Error;
+
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use clap::ValueEnum;
+use rust_xlsxwriter::{ExcelDateTime, Format, FormatAlign, Workbook, Worksheet};
+
+use crate::{
+    model::{BooxFile, Section},
+    text::normalise_whitespace,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+const SHEET_NAME_LENGTH: usize = 31;
+const INVALID_SHEET_NAME_CHARS: &[char] = &['[', ']', ':', '*', '?', '/', '\\'];
+const COMBINED_SHEET: &str = "Highlights";
+
+/// Columns before the highlight columns on the combined sheet
+const BOOK_COLUMNS: [(&str, f64); 2] = [("Title", 30.0), ("Author", 20.0)];
+const HIGHLIGHT_COLUMNS: [(&str, f64); 6] = [
+    ("Chapter", 25.0),
+    ("Page", 8.0),
+    ("Timestamp", 17.0),
+    ("Highlight", 80.0),
+    ("Note", 40.0),
+    ("ID", 18.0),
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SheetLayout {
+    /// A sheet per book
+    #[default]
+    Book,
+    /// A single sheet with the book's title and author on every row
+    Combined,
+}
+
+struct Formats {
+    header: Format,
+    text: Format,
+    wrapped: Format,
+    date: Format,
+}
+
+pub fn to_xlsx(books: &[BooxFile], layout: SheetLayout) -> Result<Vec<u8>> {
+    let formats = Formats {
+        header: Format::new().set_bold(),
+        text: Format::new().set_align(FormatAlign::Top),
+        wrapped: Format::new().set_align(FormatAlign::Top).set_text_wrap(),
+        date: Format::new()
+            .set_align(FormatAlign::Top)
+            .set_num_format("yyyy-mm-dd hh:mm"),
+    };
+    let mut workbook = Workbook::new();
+
+    match layout {
+        SheetLayout::Book => {
+            let mut names = vec![];
+            for boox in books {
+                let name = sheet_name(&boox.metadata.title, &names);
+                let sheet = workbook.add_worksheet();
+                sheet.set_name(&name)?;
+                names.push(name);
+
+                let header_rows = write_header(sheet, &HIGHLIGHT_COLUMNS, 0, &formats)?;
+                write_highlights(sheet, boox, 0, header_rows, &formats)?;
+                finish_sheet(
+                    sheet,
+                    header_rows + highlight_count(boox),
+                    HIGHLIGHT_COLUMNS.len() as u16 - 1,
+                )?;
+            }
+        }
+        SheetLayout::Combined => {
+            let sheet = workbook.add_worksheet();
+            sheet.set_name(COMBINED_SHEET)?;
+
+            let book_columns = BOOK_COLUMNS.len() as u16;
+            let columns = book_columns + HIGHLIGHT_COLUMNS.len() as u16;
+            write_header(sheet, &BOOK_COLUMNS, 0, &formats)?;
+            write_header(sheet, &HIGHLIGHT_COLUMNS, book_columns, &formats)?;
+
+            let mut row = 1;
+            for boox in books {
+                for r in row..row + highlight_count(boox) {
+                    sheet.write_string_with_format(r, 0, &boox.metadata.title, &formats.text)?;
+                    sheet.write_string_with_format(
+                        r,
+                        1,
+                        boox.metadata.author_names(),
+                        &formats.text,
+                    )?;
+                }
+                write_highlights(sheet, boox, book_columns, row, &formats)?;
+                row += highlight_count(boox);
+            }
+            finish_sheet(sheet, row, columns - 1)?;
+        }
+    }
+
+    // An empty workbook is not a valid file
+    if books.is_empty() {
+        workbook.add_worksheet().set_name(COMBINED_SHEET)?;
+    }
+
+    Ok(workbook.save_to_buffer()?)
+}
+
+/// Writes the column headers from `first_col` on and returns the number of header rows
+fn write_header(
+    sheet: &mut Worksheet,
+    columns: &[(&str, f64)],
+    first_col: u16,
+    formats: &Formats,
+) -> Result<u32> {
+    for (col, (name, width)) in (first_col..).zip(columns) {
+        sheet.write_string_with_format(0, col, *name, &formats.header)?;
+        sheet.set_column_width(col, *width)?;
+    }
+    Ok(1)
+}
+
+fn write_highlights(
+    sheet: &mut Worksheet,
+    boox: &BooxFile,
+    first_col: u16,
+    first_row: u32,
+    formats: &Formats,
+) -> Result<()> {
+    let mut chapter = "";
+    let mut row = first_row;
+
+    for section in &boox.sections {
+        let highlight = match section {
+            Section::Chapter(c) => {
+                chapter = &c.title;
+                continue;
+            }
+            Section::HL(highlight) => highlight,
+        };
+
+        sheet.write_string_with_format(row, first_col, chapter, &formats.text)?;
+        sheet.write_number_with_format(row, first_col + 1, highlight.page, &formats.text)?;
+        sheet.write_datetime_with_format(
+            row,
+            first_col + 2,
+            excel_datetime(&highlight.timestamp)?,
+            &formats.date,
+        )?;
+        sheet.write_string_with_format(
+            row,
+            first_col + 3,
+            highlight.highlight.trim_end(),
+            &formats.wrapped,
+        )?;
+        if let Some(note) = &highlight.note {
+            sheet.write_string_with_format(
+                row,
+                first_col + 4,
+                note.trim_end(),
+                &formats.wrapped,
+            )?;
+        }
+        sheet.write_string_with_format(row, first_col + 5, &highlight.id, &formats.text)?;
+
+        row += 1;
+    }
+
+    Ok(())
+}
+
+fn finish_sheet(sheet: &mut Worksheet, rows: u32, last_col: u16) -> Result<()> {
+    sheet.set_freeze_panes(1, 0)?;
+    sheet.autofilter(0, 0, rows.saturating_sub(1), last_col)?;
+    Ok(())
+}
+
+fn highlight_count(boox: &BooxFile) -> u32 {
+    let count = boox
+        .sections
+        .iter()
+        .filter(|s| matches!(s, Section::HL(_)))
+        .count();
+    u32::try_from(count).unwrap_or(u32::MAX)
+}
+
+fn excel_datetime(timestamp: &NaiveDateTime) -> Result<ExcelDateTime> {
+    Ok(ExcelDateTime::from_ymd(
+        u16::try_from(timestamp.year())?,
+        u8::try_from(timestamp.month())?,
+        u8::try_from(timestamp.day())?,
+    )?
+    .and_hms(
+        u16::try_from(timestamp.hour())?,
+        u8::try_from(timestamp.minute())?,
+        timestamp.second(),
+    )?)
+}
+
+/// A valid sheet name for `title` that isn't in `used` yet, compared case-insensitively like Excel
+fn sheet_name(title: &str, used: &[String]) -> String {
+    let cleaned: String = title
+        .chars()
+        .map(|c| {
+            if INVALID_SHEET_NAME_CHARS.contains(&c) || c.is_control() {
+                ' '
+            } else {
+                c
+            }
+        })
+        .collect();
+    let cleaned = normalise_whitespace(&cleaned);
+    let cleaned = cleaned.trim_matches('\'');
+    let base = if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("history") {
+        "Book"
+    } else {
+        cleaned
+    };
+
+    let is_used = |name: &str| used.iter().any(|u| u.to_lowercase() == name.to_lowercase());
+    let truncate = |s: &str, len: usize| s.chars().take(len).collect::<String>();
+
+    let mut name = truncate(base, SHEET_NAME_LENGTH).trim_end().to_owned();
+    let mut n = 1;
+    while is_used(&name) {
+        n += 1;
+        let suffix = format!(" ({n})");
+        let kept = truncate(base, SHEET_NAME_LENGTH - suffix.len());
+        name = format!("{}{suffix}", kept.trim_end());
+    }
+    name
+}
+
+#[test]
+fn sheet_name_test() {
+    assert_eq!(
+        sheet_name("Building a Second Brain -- A Proven Method", &[]),
+        "Building a Second Brain -- A Pr"
+    );
+    assert_eq!(sheet_name("Why? What: [Notes]", &[]), "Why What Notes");
+    assert_eq!(sheet_name("History", &[]), "Book");
+    assert_eq!(
+        sheet_name("one up", &["One Up".to_owned(), "One Up (2)".to_owned()]),
+        "one up (3)"
+    );
+    assert_eq!(
+        sheet_name(
+            "Building a Second Brain -- A Proven Method",
+            &["building a second brain -- a pr".to_owned()]
+        ),
+        "Building a Second Brain -- (2)"
+    );
+}
+
+#[test]
+fn xlsx_test() {
+    use std::io::{Cursor, Read};
+
+    use crate::parse_boox::parse_boox;
+
+    let (_, data) = parse_boox(include_str!("../test/data/data.txt")).unwrap();
+    let (_, v1) = parse_boox(include_str!("../test/data/v1.txt")).unwrap();
+    let books = [data, v1];
+
+    let read = |layout: SheetLayout, name: &str| {
+        let xlsx = to_xlsx(&books, layout).unwrap();
+        let mut zip = zip::ZipArchive::new(Cursor::new(xlsx)).unwrap();
+        let mut s = String::new();
+        zip.by_name(name).unwrap().read_to_string(&mut s).unwrap();
+        s
+    };
+
+    let workbook = read(SheetLayout::Book, "xl/workbook.xml");
+    assert!(workbook.contains(r#"name="Building a Second Brain -- A Pr""#));
+    assert!(workbook.contains(r#"name="One Up on Wall Street""#));
+
+    let sheet = read(SheetLayout::Book, "xl/worksheets/sheet1.xml");
+    assert!(sheet
+        .contains(r#"<pane ySplit="1" topLeftCell="A2" activePane="bottomLeft" state="frozen"/>"#));
+    assert!(sheet.contains(r#"<autoFilter ref="A1:F5"/>"#));
+
+    let combined = read(SheetLayout::Combined, "xl/worksheets/sheet1.xml");
+    assert!(combined.contains(r#"<autoFilter ref="A1:H9"/>"#));
+}